@@ -1,46 +1,418 @@
 use std::borrow::Borrow;
 use std::cell::{Cell, RefCell};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
 
 use raylib::prelude::*;
 
+use glam::Vec2;
+use illogical::chip::{self, Chip};
+use illogical::circuit::Circuit;
+use illogical::equivalence::{self, Method, Report};
+use illogical::eval::{self, DependencyGraph, Evaluator};
+use illogical::expr::{self, Expr};
+use illogical::file::{CircuitFile, Contents, Routes};
+use illogical::logic::{Logic, Value};
+use illogical::minimize::{self, Implicant, MAX_MAP_VARIABLES, MAX_VARIABLES};
+use illogical::route::{self, Route, WireStyle};
+use illogical::simulation::Simulation;
+use illogical::subnetwork::Subnetwork;
+use illogical::synth;
+use illogical::truth_table::TruthTable;
+use illogical::wire::*;
+
 use crate::PIN_RADIUS;
-use crate::id_salt;
-use crate::wire::*;
+use crate::history::{Command, Edit, History};
+
+/// Wires and pins that are X
+const X_COLOR: [f32; 4] = [1.0, 0.2, 0.2, 1.0];
+/// Wires and pins nothing drives
+const Z_COLOR: [f32; 4] = [0.3, 0.5, 1.0, 1.0];
+/// How far copies of nodes are placed from the originals
+const DUPLICATE_OFFSET: Vec2 = Vec2::new(30.0, 30.0);
+/// How close to a wire the mouse has to be to pick it
+const WIRE_HIT_DISTANCE: f32 = 6.0;
+/// Half the size of the squares marking the waypoints of a wire
+const WAYPOINT_SIZE: f32 = 4.0;
+/// imgui keeps windows on whole pixels, node windows and pins moving by less than this were
+/// only rounded differently after panning or zooming
+const PIXEL_TOLERANCE: f32 = 1.5;
+/// Wire under the mouse
+const HOVER_COLOR: [f32; 4] = [1.0, 0.8, 0.2, 1.0];
+/// Wires and pins that are high or low, black would vanish into the background
+const HIGH_COLOR: [f32; 4] = [0.3, 1.0, 0.3, 1.0];
+const LOW_COLOR: [f32; 4] = [0.1, 0.4, 0.1, 1.0];
+/// Buses with every bit known
+const BUS_COLOR: [f32; 4] = [0.9, 0.9, 0.9, 1.0];
+/// Seconds a change takes to travel along one wire
+const PULSE_DURATION: f32 = 0.3;
+/// Pulses further down a chain of gates than this all start together
+const MAX_PULSE_DEPTH: usize = 8;
+/// Spacing of the background grid, in canvas units
+const GRID: f32 = 20.0;
+const MIN_ZOOM: f32 = 0.25;
+const MAX_ZOOM: f32 = 4.0;
+/// Zoom factor per notch of the mouse wheel
+const ZOOM_STEP: f32 = 1.1;
+
+fn logic_color(logic: Logic) -> [f32; 4] {
+    match logic {
+        Logic::Zero => [0.0, 0.0, 0.0, 1.0],
+        Logic::One => [1.0, 1.0, 1.0, 1.0],
+        Logic::X => X_COLOR,
+        Logic::Z => Z_COLOR,
+    }
+}
+
+/// Colour of a wire or pin carrying `value`, white before anything was evaluated
+fn value_color(value: Option<Value>, width: u8) -> [f32; 4] {
+    let Some(value) = value else {
+        return [1.0, 1.0, 1.0, 1.0];
+    };
+
+    match width {
+        1 => match value.bit(0) {
+            Logic::Zero => LOW_COLOR,
+            Logic::One => HIGH_COLOR,
+            Logic::X => X_COLOR,
+            Logic::Z => Z_COLOR,
+        },
+        _ if value.z & mask(width) == mask(width) => Z_COLOR,
+        _ if !value.is_known(width) => X_COLOR,
+        _ => BUS_COLOR,
+    }
+}
+
+fn color(color: [f32; 4]) -> Color {
+    let [r, g, b, a] = color.map(|c| (c * 255.0) as u8);
+    Color::new(r, g, b, a)
+}
+
+/// A change travelling along the wires leaving an output
+#[derive(Debug, Clone, Copy)]
+struct Pulse {
+    /// Time the pulse leaves the output, later for outputs further down the chain of causes
+    start: f32,
+    /// Value before the change, still shown ahead of the pulse
+    from: Value,
+}
+
+/// The model uses glam, raylib has its own vector type
+fn vector2(v: Vec2) -> Vector2 {
+    Vector2::new(v.x, v.y)
+}
+
+fn socket_width(nodes: &Circuit, socket: SocketRef) -> Option<u8> {
+    nodes.socket(socket).map(|socket| socket.borrow().width)
+}
+
+/// Where a socket was drawn during the last frame
+fn socket_position(nodes: &Circuit, socket: SocketRef) -> Option<Vector2> {
+    nodes
+        .socket(socket)?
+        .borrow()
+        .absolute_position
+        .map(vector2)
+}
+
+/// Drops the wires whose sockets were removed or no longer have the same width
+fn prune_edges(nodes: &mut Circuit, edit: &mut Edit) {
+    let stale = nodes
+        .edges()
+        .iter()
+        .copied()
+        .filter(|&Edge { from, to }| {
+            let from = socket_width(nodes, from);
+            from.is_none() || from != socket_width(nodes, to)
+        })
+        .collect::<HashSet<_>>();
+
+    edit.retain_edges(nodes, |edge| !stale.contains(&edge));
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FileDialog {
+    Open,
+    SaveAs,
+}
 
-pub type DependencyGraph = HashMap<usize, Vec<(SocketKind, usize)>>;
+#[derive(Debug, Clone)]
+enum FileAction {
+    New,
+    Open(PathBuf),
+    Save(PathBuf),
+}
+
+/// State of the "create chip" dialog
+#[derive(Debug, Clone)]
+struct ChipDialog {
+    name: String,
+    /// Input and Display nodes of the selection that become pins
+    exposed: HashSet<NodeId>,
+    /// Swap the selected nodes for an instance of the new chip
+    replace: bool,
+}
 
-// pub struct Evaluator {
-//     pub values: HashMap<SocketRef, bool>, // SocketRef -> bool
-// }
+/// Maps canvas coordinates, in which node positions are stored, to the screen
+#[derive(Debug, Clone, Copy)]
+struct Camera {
+    /// Screen position of the canvas origin
+    offset: Vec2,
+    zoom: f32,
+}
 
-fn build_dependency_graph(app: &App) -> DependencyGraph {
-    let mut deps: DependencyGraph = HashMap::new();
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            offset: Vec2::ZERO,
+            zoom: 1.0,
+        }
+    }
+}
 
-    for edge in &app.edges {
-        let Edge {
-            from: output,
-            to: input,
-        } = edge.borrow().0;
+impl Camera {
+    fn to_screen(self, point: Vec2) -> Vec2 {
+        point * self.zoom + self.offset
+    }
 
-        deps.entry(input.node_id).or_default().push((SocketKind::Output, output.node_id));
-        deps.entry(output.node_id).or_default().push((SocketKind::Input, input.node_id));
+    fn to_canvas(self, point: Vec2) -> Vec2 {
+        (point - self.offset) / self.zoom
     }
+}
+
+/// Change to nodes requested from their context menu or a shortcut
+#[derive(Debug, Clone)]
+enum NodeAction {
+    Delete(Vec<NodeId>),
+    Duplicate(Vec<NodeId>),
+    Rename(NodeId, String),
+    /// Wires stay on the pins the new kind still has
+    ChangeKind(NodeId, NodeKind),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ClipboardAction {
+    Copy,
+    /// Paste with the top left corner of the fragment at this screen position
+    Paste(Vector2),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum HistoryAction {
+    Undo,
+    Redo,
+    /// Undo or redo until this many entries are done
+    Jump(usize),
+}
+
+#[derive(Debug, Clone)]
+enum ChipAction {
+    Create(ChipDialog),
+    Edit(usize),
+    Finish { save: bool },
+}
+
+/// State of the "synthesize" dialog
+#[derive(Debug, Clone, Default)]
+struct SynthDialog {
+    text: String,
+    nand_only: bool,
+    // why the text couldn't be parsed
+    error: Option<String>,
+}
+
+/// Parsed equations from the synthesize dialog
+struct Synthesis {
+    equations: Vec<(String, Expr)>,
+    nand_only: bool,
+}
+
+/// Truth table window, the table is a snapshot taken when it was opened or refreshed
+struct TruthTableView {
+    table: TruthTable,
+    /// Row indices in the order they are shown
+    order: Vec<usize>,
+    // export destination, the extension picks the format
+    path: String,
+}
 
-    deps
+/// Formulas of one Display node in the expressions window
+struct Expression {
+    display: String,
+    /// Infix, sum of products and product of sums as (label, formula), or why there is no
+    /// formula. The simplified forms are missing if there are too many inputs.
+    forms: Result<Vec<(&'static str, String)>, String>,
+}
+
+/// Circuit on one side of the equivalence dialog
+#[derive(Debug, Clone)]
+enum EquivalenceSide {
+    Canvas,
+    File(String),
+    /// Copy of the nodes selected when it was picked and the wires between them
+    Selection(Vec<Node>, Vec<Edge>),
+}
+
+/// State of the "check equivalence" dialog
+struct EquivalenceDialog {
+    sides: [EquivalenceSide; 2],
+    result: Option<Result<Report, String>>,
+}
+
+/// One output of the sub-network in the minimize window
+struct MinimizedOutput {
+    name: String,
+    minterms: Vec<u32>,
+    /// Rows where the output is X or Z
+    dont_cares: Vec<u32>,
+    primes: Vec<Implicant>,
+    /// Prime implicants making up the minimal sum of products
+    cover: Vec<Implicant>,
+    sop: String,
+    pos: String,
+}
+
+/// Minimize window, a snapshot of the selected gates (or all of them) like the truth table
+struct MinimizeView {
+    subnetwork: Subnetwork,
+    /// Input names, the first is the most significant bit of a row number
+    vars: Vec<String>,
+    outputs: Vec<MinimizedOutput>,
+    /// Output shown in the map
+    output: usize,
+    // implicant under the mouse in the list, highlighted in the map
+    hovered: Option<Implicant>,
+    nand_only: bool,
+}
+
+/// Background colors of the implicants in a Karnaugh map
+const GROUP_COLORS: [[f32; 4]; 6] = [
+    [0.9, 0.3, 0.3, 0.45],
+    [0.3, 0.8, 0.3, 0.45],
+    [0.3, 0.5, 0.95, 0.45],
+    [0.9, 0.8, 0.2, 0.45],
+    [0.8, 0.3, 0.9, 0.45],
+    [0.2, 0.85, 0.85, 0.45],
+];
+
+/// Orthogonal routes from the last frames in canvas coordinates, panning and zooming leave
+/// them as they are
+#[derive(Debug, Default)]
+struct RouteCache {
+    /// Node windows the routes go around
+    obstacles: HashMap<NodeId, (Vec2, Vec2)>,
+    /// Corners of every wire's route by the points it was routed through, dropped when a node
+    /// moves into or out of its way
+    paths: HashMap<Edge, (Vec<Vec2>, Vec<Vec2>)>,
+}
+
+/// Circuit put aside while a chip is open for editing
+struct Editing {
+    /// Index into `App::chips`
+    chip: usize,
+    nodes: Circuit,
+    routes: Routes,
+    file_path: Option<PathBuf>,
+    history: History,
+    ids: IdAllocator,
+    // pins of the chip being edited
+    inputs: Vec<NodeId>,
+    outputs: Vec<NodeId>,
 }
 
 pub struct App {
-    // TODO: use a hash map?
-    pub nodes: RefCell<Vec<Node>>,
+    pub nodes: RefCell<Circuit>,
+    /// Hands out the ids of the circuit on the canvas
+    pub ids: RefCell<IdAllocator>,
     pub mouse_pos: Vector2,
-    pub edges: Vec<RefCell<(Edge, Vector2, Vector2)>>,
     pub ongoing: Option<(Vector2, SocketRef)>,
     pub right_click_window: Cell<Option<Vector2>>,
-    pub dependency_graph: DependencyGraph,
+    pub evaluator: RefCell<Evaluator>,
+    pub simulation: Simulation,
+
+    /// File the circuit was last opened from or saved to
+    pub file_path: RefCell<Option<PathBuf>>,
+    file_dialog: RefCell<Option<(FileDialog, String)>>,
+    // file operation requested from the ui, performed on the next `handle_events`
+    file_action: RefCell<Option<FileAction>>,
+    status: RefCell<Option<String>>,
+
+    /// Chips shown in the palette
+    pub chips: RefCell<Vec<Chip>>,
+    /// Nodes selected with shift + click or a selection box
+    pub selected: RefCell<HashSet<NodeId>>,
+    chip_dialog: RefCell<Option<ChipDialog>>,
+    chip_action: RefCell<Option<ChipAction>>,
+    editing: Vec<Editing>,
+    // (node id, width) requested from the ui
+    resize: Cell<Option<(NodeId, u8)>>,
+    truth_table: RefCell<Option<TruthTableView>>,
+    // snapshot like the truth table
+    expressions: RefCell<Option<Vec<Expression>>>,
+    synth_dialog: RefCell<Option<SynthDialog>>,
+    synthesis: RefCell<Option<Synthesis>>,
+    minimize: RefCell<Option<MinimizeView>>,
+    equivalence: RefCell<Option<EquivalenceDialog>>,
+    // swap the gates in the minimize window for the minimized ones
+    replace_minimized: Cell<bool>,
+
+    /// Undo history of the circuit on the canvas
+    history: RefCell<History>,
+    history_action: Cell<Option<HistoryAction>>,
+    show_history: Cell<bool>,
+
+    node_action: RefCell<Option<NodeAction>>,
+    clipboard_action: Cell<Option<ClipboardAction>>,
+    // node window under the mouse during the last frame
+    hovered: Cell<Option<NodeId>>,
+    // open the context menu of this node on the next frame
+    node_menu: Cell<Option<NodeId>>,
+    // name being typed in the context menu
+    rename: RefCell<String>,
+    // a text field has focus, shortcuts are left alone
+    typing: Cell<bool>,
+    // the mouse is over a window, wires below it can't be picked
+    over_ui: Cell<bool>,
+    /// Wire under the mouse
+    hovered_wire: Cell<Option<Edge>>,
+    // removal of the wire being dragged by its end, finished when it is let go
+    reroute: Option<Edit>,
+
+    camera: Cell<Camera>,
+    /// Round node positions to the grid
+    snap: Cell<bool>,
+    // node moved by the current drag, snapped when it ends
+    dragged: Cell<Option<NodeId>>,
+    // node whose window was moved during the last frame, with its position before
+    drag: Cell<Option<(NodeId, Vec2)>>,
+    // corner where a selection box was started
+    rubber_band: Option<Vector2>,
+    // screen rectangles of the node windows during the last frame
+    node_rects: RefCell<HashMap<NodeId, (Vec2, Vec2)>>,
+
+    /// Style and waypoints of the wires that aren't plain curves
+    routes: Routes,
+    /// Style given to new wires
+    wire_style: Cell<WireStyle>,
+    // waypoint being dragged, with the route of its wire before the drag
+    waypoint_drag: Option<(Edge, usize, Route)>,
+    route_cache: RefCell<RouteCache>,
+    // shape of every wire as it was last drawn, what the mouse is pointing at
+    wires: RefCell<HashMap<Edge, Wire>>,
+
+    /// Show changes travelling along the wires
+    animate: Cell<bool>,
+    // seconds since the app started, drives the pulses
+    time: f32,
+    // output values after the last evaluation, to find the ones that changed
+    last_values: RefCell<HashMap<SocketRef, Value>>,
+    pulses: RefCell<HashMap<SocketRef, Pulse>>,
 
     // re-evalutae the graph
     eval: Cell<bool>,
+    // move node windows to their stored positions on the next frame
+    reposition: Cell<bool>,
 }
 
 impl App {
@@ -48,284 +420,2625 @@ impl App {
         Self {
             ongoing: None,
             eval: false.into(),
+            reposition: false.into(),
+            file_path: None.into(),
+            file_dialog: None.into(),
+            file_action: None.into(),
+            status: None.into(),
+            chips: vec![].into(),
+            selected: HashSet::new().into(),
+            chip_dialog: None.into(),
+            chip_action: None.into(),
+            editing: vec![],
+            resize: None.into(),
+            truth_table: None.into(),
+            expressions: None.into(),
+            synth_dialog: None.into(),
+            synthesis: None.into(),
+            minimize: None.into(),
+            equivalence: None.into(),
+            history: History::default().into(),
+            history_action: None.into(),
+            show_history: false.into(),
+            node_action: None.into(),
+            clipboard_action: None.into(),
+            hovered: None.into(),
+            node_menu: None.into(),
+            rename: String::new().into(),
+            typing: false.into(),
+            over_ui: false.into(),
+            hovered_wire: None.into(),
+            reroute: None,
+            camera: Camera::default().into(),
+            snap: false.into(),
+            dragged: None.into(),
+            drag: None.into(),
+            rubber_band: None,
+            routes: Routes::new(),
+            wire_style: WireStyle::Curved.into(),
+            waypoint_drag: None,
+            route_cache: RouteCache::default().into(),
+            wires: HashMap::new().into(),
+            node_rects: HashMap::new().into(),
+            animate: false.into(),
+            time: 0.0,
+            last_values: HashMap::new().into(),
+            pulses: HashMap::new().into(),
+            replace_minimized: false.into(),
             mouse_pos: Vector2::zero(),
-            dependency_graph: DependencyGraph::new(),
-            edges: vec![],
+            evaluator: Evaluator::new().into(),
+            simulation: Simulation::new(),
             right_click_window: None.into(),
-            nodes: vec![
-                // Node {
-                //     id: id_salt(),
-                //     position: Vector2::zero().into(),
-                //     name: "Sample node1".to_string(),
-                //     inputs: vec![
-                //         Socket {
-                //             id: id_salt(),
-                //             kind: SocketKind::Input,
-                //             name: "hydrogen".to_string(),
-                //             absolute_position: None,
-                //         }
-                //         .into(),
-                //         Socket {
-                //             id: id_salt(),
-                //             kind: SocketKind::Input,
-                //             name: "chlorine".to_string(),
-                //             absolute_position: None,
-                //         }
-                //         .into(),
-                //     ],
-
-                //     outputs: vec![
-                //         Socket {
-                //             id: id_salt(),
-                //             name: "hydrochloric acid".to_string(),
-                //             kind: SocketKind::Output,
-                //             absolute_position: None,
-                //         }
-                //         .into(),
-                //     ],
-                // },
-                // Node {
-                //     id: id_salt(),
-                //     position: Vector2::zero().into(),
-                //     name: "Sample node 2".to_string(),
-                //     inputs: vec![
-                //         Socket {
-                //             id: id_salt(),
-                //             kind: SocketKind::Input,
-                //             name: "hydrochloric acid".to_string(),
-                //             absolute_position: None,
-                //         }
-                //         .into(),
-                //     ],
-
-                //     outputs: vec![
-                //         Socket {
-                //             id: id_salt(),
-                //             name: "idk what to put here".to_string(),
-                //             kind: SocketKind::Output,
-                //             absolute_position: None,
-                //         }
-                //         .into(),
-                //     ],
-                // },
-                // Node {
-                //     id: id_salt(),
-                //     position: Vector2::zero().into(),
-                //     name: "hydrogen".to_string(),
-                //     inputs: vec![],
-                //     outputs: vec![
-                //         Socket {
-                //             id: id_salt(),
-                //             name: "hydrogen".to_string(),
-                //             kind: SocketKind::Output,
-                //             absolute_position: None,
-                //         }
-                //         .into(),
-                //     ],
-                // },
-                // Node {
-                //     id: id_salt(),
-                //     position: Vector2::zero().into(),
-                //     name: "chlorine".to_string(),
-                //     inputs: vec![],
-                //     outputs: vec![
-                //         Socket {
-                //             id: id_salt(),
-                //             name: "chlorine".to_string(),
-                //             kind: SocketKind::Output,
-                //             absolute_position: None,
-                //         }
-                //         .into(),
-                //     ],
-                // },
-            ]
-            .into(),
+            ids: IdAllocator::new().into(),
+            nodes: Circuit::new().into(),
+        }
+    }
+
+    /// Replaces the current circuit with the one stored at `path`
+    pub fn open(&mut self, path: &Path) -> io::Result<()> {
+        let mut file = CircuitFile::load(path)?;
+        *self.chips.get_mut() = std::mem::take(&mut file.chips);
+        // ids are kept as they were saved
+        let Contents {
+            nodes,
+            edges,
+            routes,
+            ..
+        } = file.into_circuit()?;
+        *self.ids.get_mut() = IdAllocator::after(&nodes);
+
+        self.editing.clear();
+        *self.history.get_mut() = History::default();
+        *self.nodes.get_mut() = nodes.into();
+        self.nodes.get_mut().rewire(edges);
+        self.routes = routes;
+        *self.file_path.get_mut() = Some(path.to_path_buf());
+
+        self.reset();
+        Ok(())
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let nodes = self.nodes.borrow();
+        let mut file = CircuitFile::new(&nodes, nodes.edges().iter().copied());
+        file.chips = self.chips.borrow().clone();
+        file.set_routes(&self.routes);
+        file.save(path)?;
+
+        *self.file_path.borrow_mut() = Some(path.to_path_buf());
+        Ok(())
+    }
+
+    fn new_circuit(&mut self) {
+        self.editing.clear();
+        *self.history.get_mut() = History::default();
+        self.chips.get_mut().clear();
+        self.nodes.get_mut().clear();
+        *self.ids.get_mut() = IdAllocator::new();
+        self.routes.clear();
+        *self.file_path.get_mut() = None;
+
+        self.reset();
+    }
+
+    /// Brings the derived state up to date after the circuit was replaced
+    fn reset(&mut self) {
+        self.ongoing = None;
+        self.waypoint_drag = None;
+        self.selected.get_mut().clear();
+        self.hovered_wire.set(None);
+        self.evaluator = Evaluator::new().into();
+        self.last_values.get_mut().clear();
+        self.pulses.get_mut().clear();
+        self.simulation.tick.set(0);
+        self.reposition.set(true);
+        self.eval.set(true);
+    }
+
+    /// Records an edit that was already carried out
+    fn commit(&self, label: String, edit: Edit) {
+        self.history.borrow_mut().push(label, Command::Edit(edit));
+    }
+
+    fn handle_history(&mut self) {
+        let Some(action) = self.history_action.take() else {
+            return;
+        };
+
+        let history = self.history.get_mut();
+        let target = match action {
+            HistoryAction::Undo => history.done.len().saturating_sub(1),
+            HistoryAction::Redo => history.done.len() + 1,
+            HistoryAction::Jump(done) => done,
+        };
+
+        let nodes = self.nodes.get_mut();
+        while history.done.len() > target {
+            let Some(command) = history.undo() else {
+                break;
+            };
+            command.apply(nodes, &mut self.routes, false);
+        }
+
+        while history.done.len() < target {
+            let Some(command) = history.redo() else {
+                break;
+            };
+            command.apply(nodes, &mut self.routes, true);
+        }
+
+        self.ongoing = None;
+        self.hovered_wire.set(None);
+        self.reposition.set(true);
+        self.eval.set(true);
+    }
+
+    /// Saves to the current file, asks for a path if there is none
+    fn save_current(&self) {
+        match self.file_path.borrow().clone() {
+            Some(path) => *self.file_action.borrow_mut() = Some(FileAction::Save(path)),
+            None => self.open_file_dialog(FileDialog::SaveAs),
+        }
+    }
+
+    fn open_file_dialog(&self, dialog: FileDialog) {
+        let path = self
+            .file_path
+            .borrow()
+            .as_ref()
+            .map(|path| path.display().to_string())
+            .unwrap_or_else(|| "circuit.json".to_string());
+
+        *self.file_dialog.borrow_mut() = Some((dialog, path));
+    }
+
+    fn handle_file_action(&mut self) {
+        let action = self.file_action.get_mut().take();
+        if action.is_some() && !self.editing.is_empty() {
+            *self.status.get_mut() = Some("finish editing the chip first".to_string());
+            return;
+        }
+
+        let (result, verb, path) = match action {
+            None => return,
+            Some(FileAction::New) => {
+                self.new_circuit();
+                *self.status.get_mut() = None;
+                return;
+            }
+
+            Some(FileAction::Open(path)) => (self.open(&path), "opened", path),
+            Some(FileAction::Save(path)) => (self.save(&path), "saved", path),
+        };
+
+        *self.status.get_mut() = Some(match result {
+            Ok(()) => format!("{verb} {}", path.display()),
+            Err(err) => format!("{}: {err}", path.display()),
+        });
+    }
+
+    fn handle_chip_action(&mut self) {
+        match self.chip_action.get_mut().take() {
+            None => {}
+            Some(ChipAction::Create(dialog)) => self.create_chip(dialog),
+            Some(ChipAction::Edit(idx)) => self.edit_chip(idx),
+            Some(ChipAction::Finish { save }) => self.finish_chip(save),
+        }
+    }
+
+    /// Packages the selected nodes and the wires between them into a new chip
+    fn create_chip(&mut self, dialog: ChipDialog) {
+        let selected = self.selected.get_mut().clone();
+        if selected.is_empty() {
+            return;
+        }
+
+        let nodes = self
+            .nodes
+            .get_mut()
+            .iter()
+            .filter(|node| selected.contains(&node.id()))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let edges = self
+            .nodes
+            .get_mut()
+            .edges()
+            .iter()
+            .copied()
+            .filter(|edge| {
+                selected.contains(&edge.from.node_id) && selected.contains(&edge.to.node_id)
+            })
+            .collect::<Vec<_>>();
+
+        let inputs = pins_by_height(&nodes, |node| {
+            dialog.exposed.contains(&node.id()) && matches!(node.kind, NodeKind::Input(_))
+        });
+        let outputs = pins_by_height(&nodes, |node| {
+            dialog.exposed.contains(&node.id()) && matches!(node.kind, NodeKind::Display(_))
+        });
+
+        let position = nodes
+            .iter()
+            .map(|node| *node.position.borrow())
+            .reduce(Vec2::min)
+            .unwrap_or_default();
+
+        let chip = Chip::new(dialog.name, nodes, edges, inputs, outputs);
+
+        if dialog.replace {
+            let mut edit = Edit::default();
+            let instance =
+                NodeKind::Chip(Box::new(chip.clone())).build(position, self.ids.get_mut());
+
+            // wires leaving through an exposed display now leave from the matching chip pin
+            let nodes = self.nodes.get_mut();
+            let leaving = nodes
+                .edges()
+                .iter()
+                .filter(|edge| !selected.contains(&edge.to.node_id))
+                .filter_map(|&edge| {
+                    let pin = chip
+                        .outputs
+                        .iter()
+                        .position(|&id| id == edge.from.node_id)?;
+                    let from = SocketRef {
+                        node_id: instance.id(),
+                        socket_id: instance.outputs[pin].borrow().id,
+                    };
+                    Some(Edge { from, ..edge })
+                })
+                .collect::<Vec<_>>();
+
+            edit.remove_nodes(nodes, |id| selected.contains(&id));
+            edit.add_nodes(nodes, [instance]);
+            edit.connect(nodes, leaving);
+
+            self.selected.get_mut().clear();
+            self.commit(format!("Create chip {}", chip.name), edit);
+            self.eval.set(true);
+        }
+
+        self.chips.get_mut().push(chip);
+    }
+
+    /// Opens a chip from the palette on the canvas, the current circuit is put aside
+    fn edit_chip(&mut self, idx: usize) {
+        let Some(chip) = self.chips.get_mut().get(idx).cloned() else {
+            return;
+        };
+
+        // the chip is a circuit of its own, nodes added while editing continue after its ids
+        let file = CircuitFile::new(&chip.nodes, chip.nodes.edges().iter().copied());
+        let Contents { nodes, edges, .. } = match file.into_circuit() {
+            Ok(contents) => contents,
+            Err(err) => {
+                *self.status.get_mut() = Some(format!("can't edit {}: {err}", chip.name));
+                return;
+            }
+        };
+        let ids = IdAllocator::after(&nodes);
+        let mut nodes = Circuit::from(nodes);
+        nodes.rewire(edges);
+
+        self.editing.push(Editing {
+            chip: idx,
+            nodes: std::mem::replace(self.nodes.get_mut(), nodes),
+            routes: std::mem::take(&mut self.routes),
+            file_path: self.file_path.get_mut().take(),
+            history: std::mem::take(self.history.get_mut()),
+            ids: std::mem::replace(self.ids.get_mut(), ids),
+            inputs: chip.inputs,
+            outputs: chip.outputs,
+        });
+
+        self.reset();
+    }
+
+    /// Closes the chip being edited, when saving the instances on the restored canvas are
+    /// updated as well
+    fn finish_chip(&mut self, save: bool) {
+        let Some(editing) = self.editing.pop() else {
+            return;
+        };
+
+        let nodes = std::mem::replace(self.nodes.get_mut(), editing.nodes);
+        self.routes = editing.routes;
+        *self.file_path.get_mut() = editing.file_path;
+        *self.history.get_mut() = editing.history;
+        *self.ids.get_mut() = editing.ids;
+
+        if save {
+            let edges = nodes.edges().to_vec();
+
+            // pins that still exist keep their order, new ones go at the end
+            let pins = |old: &[NodeId], is_pin: fn(&NodeKind) -> bool| {
+                let mut pins = old
+                    .iter()
+                    .copied()
+                    .filter(|&id| nodes.get(id).is_some_and(|node| is_pin(&node.kind)))
+                    .collect::<Vec<_>>();
+
+                pins.extend(pins_by_height(&nodes, |node| {
+                    is_pin(&node.kind) && !old.contains(&node.id())
+                }));
+                pins
+            };
+
+            let inputs = pins(&editing.inputs, |kind| matches!(kind, NodeKind::Input(_)));
+            let outputs = pins(&editing.outputs, |kind| {
+                matches!(kind, NodeKind::Display(_))
+            });
+
+            let chips = self.chips.get_mut();
+            let name = chips[editing.chip].name.clone();
+            let mut chip = Chip::new(name, nodes.into_vec(), edges, inputs, outputs);
+            chip.id = chips[editing.chip].id;
+            chips[editing.chip] = chip.clone();
+
+            // copies nested in other chips, and in the circuits still put aside for editing
+            for (idx, other) in chips.iter_mut().enumerate() {
+                if idx != editing.chip {
+                    other.update_instances(&chip);
+                }
+            }
+            for editing in &mut self.editing {
+                if !chip::update_instances(&mut editing.nodes, &mut editing.ids, &chip).is_empty() {
+                    // left out of the history that was put aside with it
+                    prune_edges(&mut editing.nodes, &mut Edit::default());
+                }
+            }
+
+            let mut edit = Edit::default();
+            let (nodes, ids) = (self.nodes.get_mut(), self.ids.get_mut());
+            let instances = nodes
+                .iter()
+                .filter(|node| matches!(node.kind, NodeKind::Chip(_)))
+                .map(|node| node.id())
+                .collect::<Vec<_>>();
+            for id in instances {
+                edit.update(nodes, id, |node| chip::update_instance(node, ids, &chip));
+            }
+
+            prune_edges(nodes, &mut edit);
+            self.commit(format!("Update {} instances", chip.name), edit);
+        }
+
+        self.reset();
+    }
+
+    fn handle_resize(&mut self) {
+        let Some((id, width)) = self.resize.take() else {
+            return;
+        };
+
+        let (nodes, ids) = (self.nodes.get_mut(), self.ids.get_mut());
+        let Some(label) = nodes
+            .get(id)
+            .map(|node| format!("Resize {} to {width} bits", node.name))
+        else {
+            return;
+        };
+
+        let mut edit = Edit::default();
+        edit.update(nodes, id, |node| {
+            node.set_width(width, ids);
+            true
+        });
+        prune_edges(nodes, &mut edit);
+        self.commit(label, edit);
+        self.eval.set(true);
+    }
+
+    /// Adds the circuit for the equations from the synthesize dialog to the right of the existing
+    /// nodes, the new nodes end up selected
+    fn handle_synthesis(&mut self) {
+        let Some(Synthesis {
+            equations,
+            nand_only,
+        }) = self.synthesis.get_mut().take()
+        else {
+            return;
+        };
+
+        let mut edit = Edit::default();
+        let nodes = self.nodes.get_mut();
+        let origin = nodes
+            .iter()
+            .map(|node| *node.position.borrow())
+            .reduce(|a, b| Vec2::new(a.x.max(b.x), a.y.min(b.y)))
+            .map_or(Vec2::new(50.0, 50.0), |corner| {
+                corner + Vec2::new(250.0, 0.0)
+            });
+
+        let (new_nodes, edges) =
+            synth::synthesize(&equations, nand_only, origin, self.ids.get_mut());
+
+        *self.selected.get_mut() = new_nodes.iter().map(|node| node.id()).collect();
+        *self.status.get_mut() = Some(format!("synthesized {} nodes", new_nodes.len()));
+        edit.add_nodes(nodes, new_nodes);
+        edit.connect(nodes, edges);
+
+        self.commit("Synthesize".to_string(), edit);
+        self.eval.set(true);
+    }
+
+    /// Replaces the gates of the minimize window with the minimal sum of products of each output
+    fn handle_replace_minimized(&mut self) {
+        if !self.replace_minimized.take() {
+            return;
+        }
+
+        let Some(view) = self.minimize.get_mut().take() else {
+            return;
+        };
+
+        let mut edit = Edit::default();
+        let subnetwork = &view.subnetwork;
+        let vars = subnetwork.variables();
+        let outputs = view
+            .outputs
+            .iter()
+            .map(|output| minimize::sum_of_products(&output.cover, &vars))
+            .collect::<Vec<_>>();
+
+        let nodes = self.nodes.get_mut();
+        let origin = nodes
+            .iter()
+            .filter(|node| subnetwork.nodes.contains(&node.id()))
+            .map(|node| *node.position.borrow())
+            .reduce(Vec2::min)
+            .unwrap_or_default();
+
+        let (new_nodes, edges) =
+            subnetwork.replacement(&outputs, view.nand_only, origin, self.ids.get_mut());
+
+        *self.status.get_mut() = Some(format!(
+            "replaced {} gates with {}",
+            subnetwork.nodes.len(),
+            new_nodes.len()
+        ));
+        *self.selected.get_mut() = new_nodes.iter().map(|node| node.id()).collect();
+
+        edit.remove_nodes(nodes, |id| subnetwork.nodes.contains(&id));
+        edit.add_nodes(nodes, new_nodes);
+        edit.connect(nodes, edges);
+
+        // the circuit may have changed since the window was opened
+        prune_edges(nodes, &mut edit);
+        self.commit("Minimize gates".to_string(), edit);
+        self.eval.set(true);
+    }
+
+    fn handle_node_action(&mut self) {
+        let Some(action) = self.node_action.get_mut().take() else {
+            return;
+        };
+
+        let mut edit = Edit::default();
+        let nodes = self.nodes.get_mut();
+        let name = |id: NodeId| {
+            let node = nodes.get(id);
+            node.map_or_else(String::new, |node| node.label(nodes))
+        };
+
+        let label = match action {
+            NodeAction::Delete(ids) => {
+                let label = match ids.as_slice() {
+                    [id] => format!("Delete {}", name(*id)),
+                    _ => format!("Delete {} nodes", ids.len()),
+                };
+
+                edit.remove_nodes(nodes, |id| ids.contains(&id));
+                self.selected.get_mut().retain(|id| !ids.contains(id));
+                label
+            }
+
+            NodeAction::Duplicate(ids) => {
+                let label = match ids.as_slice() {
+                    [id] => format!("Duplicate {}", name(*id)),
+                    _ => format!("Duplicate {} nodes", ids.len()),
+                };
+
+                let originals = nodes
+                    .iter()
+                    .filter(|node| ids.contains(&node.id()))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                let edges = nodes
+                    .edges()
+                    .iter()
+                    .copied()
+                    .filter(|edge| {
+                        ids.contains(&edge.from.node_id) && ids.contains(&edge.to.node_id)
+                    })
+                    .collect::<Vec<_>>();
+
+                let (copies, edges, routes) = duplicate(
+                    &originals,
+                    &edges,
+                    &self.routes,
+                    DUPLICATE_OFFSET,
+                    self.ids.get_mut(),
+                );
+                *self.selected.get_mut() = copies.iter().map(|node| node.id()).collect();
+                edit.add_nodes(nodes, copies);
+                edit.connect(nodes, edges);
+                edit.route(&mut self.routes, routes);
+                label
+            }
+
+            NodeAction::Rename(id, name) => {
+                let mut label = String::new();
+                let renamed = edit.update(nodes, id, |node| {
+                    if name.is_empty() || name == node.name {
+                        return false;
+                    }
+
+                    label = format!("Rename {} to {name}", node.name);
+                    node.name = name;
+                    true
+                });
+                if !renamed {
+                    return;
+                }
+                label
+            }
+
+            NodeAction::ChangeKind(id, kind) => {
+                let mut label = String::new();
+                let ids = self.ids.get_mut();
+                let changed = edit.update(nodes, id, |node| {
+                    // keep names that were picked by hand
+                    if node.name == node.kind.to_string() {
+                        node.name = kind.to_string();
+                    }
+
+                    label = format!("Change {} to {kind}", node.name);
+                    node.set_kind(kind, ids);
+                    true
+                });
+                if !changed {
+                    return;
+                }
+
+                prune_edges(nodes, &mut edit);
+                label
+            }
+        };
+
+        self.commit(label, edit);
+        self.eval.set(true);
+    }
+
+    /// Copies the selection as the text of a circuit file, pastes it back with fresh ids
+    fn handle_clipboard(&mut self, rl: &mut RaylibHandle) {
+        let Some(action) = self.clipboard_action.take() else {
+            return;
+        };
+
+        match action {
+            ClipboardAction::Copy => {
+                let (nodes, edges) = self.copy_selection();
+                if nodes.is_empty() {
+                    return;
+                }
+
+                let mut file = CircuitFile::new(&nodes, edges);
+                file.set_routes(&self.routes);
+
+                let text = file.to_text();
+                match text.map(|text| rl.set_clipboard_text(&text)) {
+                    Ok(Ok(())) => {
+                        *self.status.get_mut() = Some(format!("copied {} nodes", nodes.len()));
+                    }
+                    Ok(Err(err)) => *self.status.get_mut() = Some(err.to_string()),
+                    Err(err) => *self.status.get_mut() = Some(err.to_string()),
+                }
+            }
+
+            ClipboardAction::Paste(position) => {
+                let file = rl
+                    .get_clipboard_text()
+                    .map_err(io::Error::other)
+                    .and_then(|text| CircuitFile::parse(&text));
+
+                let Contents {
+                    nodes,
+                    edges,
+                    mut routes,
+                    ..
+                } = match file {
+                    Ok(file) => file.build(self.ids.get_mut()),
+                    Err(err) => {
+                        *self.status.get_mut() =
+                            Some(format!("the clipboard doesn't hold a circuit: {err}"));
+                        return;
+                    }
+                };
+
+                let Some(corner) = nodes
+                    .iter()
+                    .map(|node| *node.position.borrow())
+                    .reduce(Vec2::min)
+                else {
+                    return;
+                };
+
+                let offset = self.place(Vec2::new(position.x, position.y)) - corner;
+                for node in &nodes {
+                    *node.position.borrow_mut() += offset;
+                }
+                move_waypoints(&mut routes, offset);
+
+                *self.selected.get_mut() = nodes.iter().map(|node| node.id()).collect();
+                let label = format!("Paste {} nodes", nodes.len());
+
+                let mut edit = Edit::default();
+                edit.add_nodes(self.nodes.get_mut(), nodes);
+                edit.connect(self.nodes.get_mut(), edges);
+                edit.route(&mut self.routes, routes);
+
+                self.commit(label, edit);
+                self.eval.set(true);
+            }
+        }
+    }
+
+    /// Selected nodes, or the one under the mouse if there are none
+    fn targets(&self) -> Vec<NodeId> {
+        let selected = self.selected.borrow();
+        match selected.is_empty() {
+            true => self.hovered.get().into_iter().collect(),
+            false => selected.iter().copied().collect(),
         }
     }
 
     pub fn handle_events(&mut self, rl: &mut RaylibHandle) {
         self.mouse_pos = rl.get_mouse_position();
+        self.handle_file_action();
+        self.handle_chip_action();
+        self.handle_resize();
+        self.handle_synthesis();
+        self.handle_replace_minimized();
+
+        if !self.typing.get() {
+            let targets = self.targets();
+            let ctrl = rl.is_key_down(KeyboardKey::KEY_LEFT_CONTROL)
+                || rl.is_key_down(KeyboardKey::KEY_RIGHT_CONTROL);
+            let shift = rl.is_key_down(KeyboardKey::KEY_LEFT_SHIFT)
+                || rl.is_key_down(KeyboardKey::KEY_RIGHT_SHIFT);
+
+            if rl.is_key_pressed(KeyboardKey::KEY_DELETE) && !targets.is_empty() {
+                *self.node_action.get_mut() = Some(NodeAction::Delete(targets));
+            } else if rl.is_key_pressed(KeyboardKey::KEY_F2) {
+                self.node_menu.set(self.hovered.get());
+            } else if ctrl && rl.is_key_pressed(KeyboardKey::KEY_D) && !targets.is_empty() {
+                *self.node_action.get_mut() = Some(NodeAction::Duplicate(targets));
+            } else if ctrl && rl.is_key_pressed(KeyboardKey::KEY_C) {
+                self.clipboard_action.set(Some(ClipboardAction::Copy));
+            } else if ctrl && rl.is_key_pressed(KeyboardKey::KEY_V) {
+                self.clipboard_action
+                    .set(Some(ClipboardAction::Paste(self.mouse_pos)));
+            } else if ctrl && rl.is_key_pressed(KeyboardKey::KEY_A) {
+                *self.selected.get_mut() =
+                    self.nodes.get_mut().iter().map(|node| node.id()).collect();
+            } else if ctrl && rl.is_key_pressed(KeyboardKey::KEY_S) {
+                self.save_current();
+            } else if ctrl && rl.is_key_pressed(KeyboardKey::KEY_O) {
+                self.open_file_dialog(FileDialog::Open);
+            } else if ctrl && rl.is_key_pressed(KeyboardKey::KEY_Z) {
+                self.history_action.set(Some(match shift {
+                    true => HistoryAction::Redo,
+                    false => HistoryAction::Undo,
+                }));
+            } else if rl.is_key_pressed(KeyboardKey::KEY_R)
+                && let Some(wire) = self.hovered_wire.get()
+            {
+                self.toggle_wire_style(wire);
+            }
+        }
+
+        self.handle_node_action();
+        self.handle_clipboard(rl);
+
+        self.handle_history();
+        self.handle_camera(rl);
+        self.hovered_wire.set(self.wire_at(self.mouse_pos));
+        self.handle_drag();
+        self.handle_waypoint_drag(rl);
+
+        if rl.is_mouse_button_released(MouseButton::MOUSE_BUTTON_LEFT)
+            && let Some(id) = self.dragged.take()
+        {
+            self.snap_nodes(id);
+        }
+
+        if rl.is_mouse_button_released(MouseButton::MOUSE_BUTTON_LEFT)
+            && let Some(start) = self.rubber_band.take()
+        {
+            let shift = rl.is_key_down(KeyboardKey::KEY_LEFT_SHIFT)
+                || rl.is_key_down(KeyboardKey::KEY_RIGHT_SHIFT);
+            self.select_box(start, self.mouse_pos, shift);
+        }
+
+        // a new click starts a new drag, moves and value changes until then are merged
+        if rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_LEFT)
+            || rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_RIGHT)
+        {
+            self.history.get_mut().seal();
+        }
+
+        if rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_RIGHT) {
+            // remove the wires of the pin if right clicked on it
+            if let Some((node, socket)) = self.get_node_and_pin(self.mouse_pos) {
+                let pin = SocketRef {
+                    node_id: node.id(),
+                    socket_id: socket.borrow().id,
+                };
+
+                let mut edit = Edit::default();
+                let nodes = self.nodes.get_mut();
+                edit.retain_edges(nodes, |Edge { from, to }| from != pin && to != pin);
+
+                if !edit.is_empty() {
+                    self.commit("Delete wire".to_string(), edit);
+                    self.eval.set(true);
+                }
+            } else if let Some((edge, idx)) = self.waypoint_at(self.mouse_pos) {
+                let before = self.routes[&edge].clone();
+                let mut after = before.clone();
+                after.waypoints.remove(idx);
+                self.set_route("Remove waypoint", edge, before, after);
+            } else if let Some(edge) = self.hovered_wire.take() {
+                let mut edit = Edit::default();
+                edit.retain_edges(self.nodes.get_mut(), |other| other != edge);
+                self.commit("Delete wire".to_string(), edit);
+                self.eval.set(true);
+            } else if let Some(id) = self.hovered.get() {
+                self.node_menu.set(Some(id));
+            } else {
+                self.right_click_window
+                    .set(match self.right_click_window.get() {
+                        None => Some(self.mouse_pos),
+                        _ => None,
+                    });
+            }
+        }
+
+        // Wire start
+        if self.ongoing.is_none() && rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_LEFT) {
+            if let Some((node, socket)) = self.get_node_and_pin(self.mouse_pos) {
+                let socket = socket.borrow();
+                let pin = SocketRef {
+                    node_id: node.id(),
+                    socket_id: socket.id,
+                };
+                let (kind, position) = (socket.kind, socket.absolute_position.unwrap());
+                drop(socket);
+
+                match kind {
+                    SocketKind::Output => self.ongoing = Some((vector2(position), pin)),
+
+                    // pick up the wire by its end to connect it somewhere else
+                    SocketKind::Input => {
+                        let nodes = self.nodes.get_mut();
+                        let Some(&edge) = nodes.edges().iter().rfind(|edge| edge.to == pin) else {
+                            return;
+                        };
+                        let Some(p0) = socket_position(nodes, edge.from) else {
+                            return;
+                        };
+
+                        let mut edit = Edit::default();
+                        edit.retain_edges(nodes, |other| other != edge);
+                        self.ongoing = Some((p0, edge.from));
+                        self.reroute = Some(edit);
+                        self.eval.set(true);
+                    }
+                }
+            } else if let Some((edge, idx)) = self.waypoint_at(self.mouse_pos) {
+                self.waypoint_drag = Some((edge, idx, self.routes[&edge].clone()));
+            } else if self
+                .hovered_wire
+                .get()
+                .is_some_and(|wire| self.add_waypoint(wire))
+            {
+                // dragged from here on like any other waypoint
+            } else if !self.over_ui.get() {
+                self.rubber_band = Some(self.mouse_pos);
+            }
+        }
+        // Wire end
+        else if self.ongoing.is_some()
+            && rl.is_mouse_button_released(MouseButton::MOUSE_BUTTON_LEFT)
+        {
+            let from = *self.ongoing.unwrap().1.borrow();
+            if let Some((node, socket)) = self.get_node_and_pin(self.mouse_pos) {
+                if !matches!(socket.borrow().kind, SocketKind::Input) {
+                    return;
+                }
+
+                let b = socket.borrow();
+                let edge = Edge {
+                    from,
+                    to: SocketRef {
+                        node_id: node.id(),
+                        socket_id: b.id,
+                    },
+                };
+
+                let width = b.width;
+                drop(b);
+
+                let from_width = socket_width(self.nodes.get_mut(), from);
+                if from_width != Some(width) {
+                    *self.status.get_mut() = Some(format!(
+                        "can't connect a {} bit output to a {width} bit input",
+                        from_width.unwrap_or(0)
+                    ));
+
+                    self.ongoing = None;
+                    self.drop_rerouted();
+                    return;
+                }
+
+                // an input pin can only be driven by a single output, unless they're all
+                // tri-state buffers sharing a bus
+                let (label, mut edit) = match self.reroute.take() {
+                    Some(edit) => ("Reroute wire", edit),
+                    None => ("Connect wire", Edit::default()),
+                };
+                let nodes = self.nodes.get_mut();
+                let tri_state = |socket: SocketRef| {
+                    let node = nodes.get(socket.node_id);
+                    node.is_some_and(|node| matches!(node.kind, NodeKind::TriState))
+                };
+
+                let shared = tri_state(edge.from);
+                let replaced = nodes
+                    .edges()
+                    .iter()
+                    .copied()
+                    .filter(|other| other.to == edge.to && !(shared && tri_state(other.from)))
+                    .collect::<HashSet<_>>();
+                edit.retain_edges(nodes, |other| !replaced.contains(&other));
+                edit.connect(nodes, [edge]);
+
+                let style = self.wire_style.get();
+                if style != WireStyle::Curved {
+                    edit.route(&mut self.routes, [(edge, Route::new(style))]);
+                }
+                self.commit(label.to_string(), edit);
+                self.eval.set(true);
+            }
+
+            self.ongoing = None;
+            self.drop_rerouted();
+        }
+    }
+
+    /// Middle drag pans, the wheel zooms around the mouse
+    fn handle_camera(&mut self, rl: &RaylibHandle) {
+        let camera = self.camera.get_mut();
+        let mouse = Vec2::new(self.mouse_pos.x, self.mouse_pos.y);
+
+        if rl.is_mouse_button_down(MouseButton::MOUSE_BUTTON_MIDDLE) {
+            let delta = rl.get_mouse_delta();
+            if delta.x != 0.0 || delta.y != 0.0 {
+                camera.offset += Vec2::new(delta.x, delta.y);
+                self.reposition.set(true);
+            }
+        }
+
+        // windows scroll their own contents
+        let wheel = rl.get_mouse_wheel_move();
+        if wheel != 0.0 && !self.over_ui.get() {
+            let anchor = camera.to_canvas(mouse);
+            camera.zoom = (camera.zoom * ZOOM_STEP.powf(wheel)).clamp(MIN_ZOOM, MAX_ZOOM);
+            camera.offset = mouse - anchor * camera.zoom;
+            self.reposition.set(true);
+        }
+    }
+
+    /// Where a node dropped at `screen` goes on the canvas
+    fn place(&self, screen: Vec2) -> Vec2 {
+        let position = self.camera.get().to_canvas(screen);
+        match self.snap.get() {
+            true => (position / GRID).round() * GRID,
+            false => position,
+        }
+    }
+
+    /// Nodes that move along with `id`, the whole selection if it is part of it
+    fn drag_group(&self, id: NodeId) -> Vec<NodeId> {
+        let selected = self.selected.borrow();
+        if !selected.contains(&id) {
+            return vec![id];
+        }
+
+        // sorted so successive moves of the same group are merged
+        let mut group = selected.iter().copied().collect::<Vec<_>>();
+        group.sort();
+        group
+    }
+
+    /// Moves the rest of the group along with the node window that was dragged
+    fn handle_drag(&mut self) {
+        let Some((id, previous)) = self.drag.take() else {
+            return;
+        };
+
+        let group = self.drag_group(id);
+        let nodes = self.nodes.get_mut();
+        let Some(dragged) = nodes.get(id) else {
+            return;
+        };
+        let offset = *dragged.position.borrow() - previous;
+
+        let label = match group.as_slice() {
+            [_] => format!("Move {}", dragged.name),
+            _ => format!("Move {} nodes", group.len()),
+        };
+
+        let mut from = vec![];
+        let mut to = vec![];
+        for id in &group {
+            let Some(node) = nodes.get(*id) else {
+                continue;
+            };
+
+            // the dragged window is already where the mouse took it
+            let mut position = node.position.borrow_mut();
+            match node.id() == dragged.id() {
+                true => from.push(previous),
+                false => {
+                    from.push(*position);
+                    *position += offset;
+                }
+            }
+            to.push(*position);
+        }
+
+        let command = Command::Move {
+            nodes: group.clone(),
+            from,
+            to,
+        };
+        self.history.get_mut().push(label, command);
+        self.dragged.set(Some(id));
+
+        if group.len() > 1 {
+            self.reposition.set(true);
+        }
+    }
+
+    /// Puts the group of a dragged node on the grid, keeping the nodes where they are relative
+    /// to each other
+    fn snap_nodes(&mut self, id: NodeId) {
+        if !self.snap.get() {
+            return;
+        }
+
+        let group = self.drag_group(id);
+        let nodes = self.nodes.get_mut();
+        let Some(dragged) = nodes.get(id) else {
+            return;
+        };
+
+        let position = *dragged.position.borrow();
+        let offset = (position / GRID).round() * GRID - position;
+        if offset == Vec2::ZERO {
+            return;
+        }
+
+        let label = match group.as_slice() {
+            [_] => format!("Move {}", dragged.name),
+            _ => format!("Move {} nodes", group.len()),
+        };
+
+        let group_nodes = group
+            .iter()
+            .filter_map(|id| nodes.get(*id))
+            .collect::<Vec<_>>();
+        let from = group_nodes
+            .iter()
+            .map(|node| *node.position.borrow())
+            .collect::<Vec<_>>();
+        for node in &group_nodes {
+            *node.position.borrow_mut() += offset;
+        }
+        let to = from.iter().map(|position| *position + offset).collect();
+
+        // merges into the drag that was just recorded
+        let command = Command::Move {
+            nodes: group,
+            from,
+            to,
+        };
+        self.history.get_mut().push(label, command);
+        self.reposition.set(true);
+    }
+
+    /// Selects the nodes whose window lies within the box, in addition to the current
+    /// selection if `add` is set
+    fn select_box(&mut self, start: Vector2, end: Vector2, add: bool) {
+        let min = Vec2::new(start.x.min(end.x), start.y.min(end.y));
+        let max = Vec2::new(start.x.max(end.x), start.y.max(end.y));
+
+        let selected = self.selected.get_mut();
+        if !add {
+            selected.clear();
+        }
+
+        for (&id, &(top_left, bottom_right)) in self.node_rects.get_mut().iter() {
+            if top_left.cmpge(min).all() && bottom_right.cmple(max).all() {
+                selected.insert(id);
+            }
+        }
+    }
+
+    /// Records the removal of a wire picked up by its end and let go away from an input
+    fn drop_rerouted(&mut self) {
+        if let Some(edit) = self.reroute.take() {
+            self.commit("Delete wire".to_string(), edit);
+        }
+    }
+
+    /// Closest wire within reach of `point`
+    fn wire_at(&self, point: Vector2) -> Option<Edge> {
+        if self.ongoing.is_some() || self.over_ui.get() {
+            return None;
+        }
+
+        let point = Vec2::new(point.x, point.y);
+        self.wires
+            .borrow()
+            .iter()
+            .map(|(&edge, wire)| (edge, wire.distance(point)))
+            .filter(|(_, distance)| *distance <= WIRE_HIT_DISTANCE)
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(edge, _)| edge)
+    }
+
+    /// Wire and index of the waypoint under `point`
+    fn waypoint_at(&self, point: Vector2) -> Option<(Edge, usize)> {
+        if self.ongoing.is_some() || self.over_ui.get() {
+            return None;
+        }
+
+        let camera = self.camera.get();
+        let point = Vec2::new(point.x, point.y);
+        self.nodes.borrow().edges().iter().find_map(|&edge| {
+            let route = self.routes.get(&edge)?;
+            if route.style != WireStyle::Orthogonal {
+                return None;
+            }
+
+            let idx = route.waypoints.iter().position(|&waypoint| {
+                camera.to_screen(waypoint).distance(point) <= WIRE_HIT_DISTANCE
+            })?;
+            Some((edge, idx))
+        })
+    }
+
+    /// Starts dragging a new waypoint on an orthogonal wire, placed where it makes the least
+    /// detour. Returns `false` for curved wires.
+    fn add_waypoint(&mut self, edge: Edge) -> bool {
+        let nodes = self.nodes.get_mut();
+        let (Some(p0), Some(p3)) = (
+            socket_position(nodes, edge.from),
+            socket_position(nodes, edge.to),
+        ) else {
+            return false;
+        };
+        let camera = self.camera.get();
+        let point = self.place(Vec2::new(self.mouse_pos.x, self.mouse_pos.y));
+
+        let route = self.routes.get_mut(&edge);
+        let Some(route) = route.filter(|route| route.style == WireStyle::Orthogonal) else {
+            return false;
+        };
+
+        let before = route.clone();
+        let idx = route.insertion_index(
+            camera.to_canvas(Vec2::new(p0.x, p0.y)),
+            camera.to_canvas(Vec2::new(p3.x, p3.y)),
+            point,
+        );
+        route.waypoints.insert(idx, point);
+        self.waypoint_drag = Some((edge, idx, before));
+        true
+    }
+
+    /// Moves the dragged waypoint along with the mouse, records the change once it is let go
+    fn handle_waypoint_drag(&mut self, rl: &RaylibHandle) {
+        let Some((edge, idx, _)) = self.waypoint_drag else {
+            return;
+        };
+
+        let point = self.place(Vec2::new(self.mouse_pos.x, self.mouse_pos.y));
+        let route = self.routes.get_mut(&edge);
+        if let Some(waypoint) = route.and_then(|route| route.waypoints.get_mut(idx)) {
+            *waypoint = point;
+        }
+
+        if rl.is_mouse_button_released(MouseButton::MOUSE_BUTTON_LEFT)
+            && let Some((edge, _, before)) = self.waypoint_drag.take()
+        {
+            let after = self.routes.get(&edge).cloned().unwrap_or_default();
+            let label = match after.waypoints.len() > before.waypoints.len() {
+                true => "Add waypoint",
+                false => "Move waypoint",
+            };
+
+            // already in place, recorded with the route from before the drag
+            self.routes.insert(edge, before.clone());
+            self.set_route(label, edge, before, after);
+        }
+    }
+
+    /// Switches a wire between curved and orthogonal, its waypoints are kept for when it
+    /// switches back
+    fn toggle_wire_style(&mut self, edge: Edge) {
+        let before = self.routes.get(&edge).cloned().unwrap_or_default();
+
+        let (style, label) = match before.style {
+            WireStyle::Curved => (WireStyle::Orthogonal, "Route wire orthogonally"),
+            WireStyle::Orthogonal => (WireStyle::Curved, "Curve wire"),
+        };
+        let after = Route {
+            style,
+            ..before.clone()
+        };
+        self.set_route(label, edge, before, after);
+    }
+
+    /// Gives a wire a new route and records the change
+    fn set_route(&mut self, label: &str, edge: Edge, from: Route, to: Route) {
+        if from == to {
+            return;
+        }
+
+        match to.is_default() {
+            true => self.routes.remove(&edge),
+            false => self.routes.insert(edge, to.clone()),
+        };
+        self.history
+            .get_mut()
+            .push(label.to_string(), Command::Route { edge, from, to });
+    }
+
+    /// Shape of a wire on screen, orthogonal ones leave the windows of their pins and then go
+    /// around every node window
+    fn wire(&self, edge: Edge, p0: Vector2, p3: Vector2) -> Wire {
+        let route = self.routes.get(&edge);
+        let Some(route) = route.filter(|route| route.style == WireStyle::Orthogonal) else {
+            return Wire::Curve(Bezier { p0, p3 });
+        };
+
+        let camera = self.camera.get();
+        let mut cache = self.route_cache.borrow_mut();
+        let start = camera.to_canvas(Vec2::new(p0.x, p0.y));
+        let end = camera.to_canvas(Vec2::new(p3.x, p3.y));
+
+        let exit = cache
+            .obstacles
+            .get(&edge.from.node_id)
+            .map_or(start.x, |(_, max)| max.x);
+        let entry = cache
+            .obstacles
+            .get(&edge.to.node_id)
+            .map_or(end.x, |(min, _)| min.x);
+
+        let mut points = vec![
+            start,
+            Vec2::new(exit.max(start.x) + route::CLEARANCE, start.y),
+        ];
+        points.extend(&route.waypoints);
+        points.extend([Vec2::new(entry.min(end.x) - route::CLEARANCE, end.y), end]);
+
+        let tolerance = PIXEL_TOLERANCE / camera.zoom;
+        let cached = cache.paths.get(&edge).filter(|(routed, _)| {
+            routed.len() == points.len()
+                && routed
+                    .iter()
+                    .zip(&points)
+                    .all(|(a, b)| a.abs_diff_eq(*b, tolerance))
+        });
+
+        let mut path = match cached {
+            Some((_, path)) => path.clone(),
+            None => {
+                let obstacles = cache.obstacles.values().copied().collect::<Vec<_>>();
+                let mut path = vec![start];
+                for leg in points[1..points.len() - 1].windows(2) {
+                    path.extend(route::orthogonal(leg[0], leg[1], &obstacles));
+                }
+                path.push(end);
+
+                let path = route::simplify(path);
+                cache.paths.insert(edge, (points, path.clone()));
+                path
+            }
+        };
+
+        for point in &mut path {
+            *point = camera.to_screen(*point);
+        }
+
+        // the ends stay on the pins whatever rounding the route was found with
+        let last = path.len() - 1;
+        path[0] = Vec2::new(p0.x, p0.y);
+        path[last] = Vec2::new(p3.x, p3.y);
+        Wire::Lines(Polyline(path))
+    }
+
+    /// Brings the obstacles of the routes up to date with the node windows drawn this frame,
+    /// dropping the routes a node moved into or out of
+    fn update_obstacles(&self) {
+        let camera = self.camera.get();
+        let tolerance = PIXEL_TOLERANCE / camera.zoom;
+        let rects = self.node_rects.borrow();
+        let cache = &mut *self.route_cache.borrow_mut();
+
+        // where nodes were and are now
+        let mut moved = vec![];
+        cache.obstacles.retain(|id, rect| {
+            let kept = rects.contains_key(id);
+            if !kept {
+                moved.push(*rect);
+            }
+            kept
+        });
+
+        for (&id, &(min, max)) in rects.iter() {
+            let rect = (camera.to_canvas(min), camera.to_canvas(max));
+            match cache.obstacles.get(&id).copied() {
+                Some(old)
+                    if old.0.abs_diff_eq(rect.0, tolerance)
+                        && old.1.abs_diff_eq(rect.1, tolerance) => {}
+                old => {
+                    moved.extend(old);
+                    moved.push(rect);
+                    cache.obstacles.insert(id, rect);
+                }
+            }
+        }
+
+        if moved.is_empty() {
+            return;
+        }
+
+        cache.paths.retain(|_, (_, path)| {
+            let min = path.iter().copied().reduce(Vec2::min).unwrap_or_default();
+            let max = path.iter().copied().reduce(Vec2::max).unwrap_or_default();
+            let (min, max) = (min - route::CLEARANCE, max + route::CLEARANCE);
+
+            !moved
+                .iter()
+                .any(|&(a, b)| a.cmplt(max).all() && b.cmpgt(min).all())
+        });
+    }
+
+    /// last item is the location of center for snapping
+    fn get_node_and_pin(&mut self, point: Vector2) -> Option<(&Node, &RefCell<Socket>)> {
+        // * 2 for snapping
+        let point = Vec2::new(point.x, point.y);
+        let radius = PIN_RADIUS * self.camera.get().zoom;
+        let pred = |i: Vec2| (i - point).length_squared() <= radius.powi(2);
+
+        self.nodes.get_mut().iter().find_map(
+            |node @ Node {
+                 inputs, outputs, ..
+             }| {
+                inputs
+                    .iter()
+                    .find(|i| (*i).borrow().absolute_position.map(pred).unwrap_or(false))
+                    .or_else(|| {
+                        outputs
+                            .iter()
+                            .find(|o| (*o).borrow().absolute_position.map(pred).unwrap_or(false))
+                    })
+                    .map(|i| (node, i))
+            },
+        )
+    }
+
+    pub fn draw(&self, d: &mut RaylibDrawHandle) {
+        self.draw_grid(d);
+
+        let nodes = self.nodes.borrow();
+        let evaluator = self.evaluator.borrow();
+
+        let mut pulses = self.pulses.borrow_mut();
+        pulses.retain(|_, pulse| self.time < pulse.start + PULSE_DURATION);
+
+        let mut wires = HashMap::new();
+        for &edge in nodes.edges() {
+            let (Some(p0), Some(p3)) = (
+                socket_position(&nodes, edge.from),
+                socket_position(&nodes, edge.to),
+            ) else {
+                continue;
+            };
+            let hovered = self.hovered_wire.get() == Some(edge);
+            let width = socket_width(&nodes, edge.from).unwrap_or(1);
+
+            // buses are drawn thicker
+            let mut thickness = match width {
+                1 => 2.0,
+                _ => 4.0,
+            };
+            if hovered {
+                thickness += 2.0;
+            }
+
+            let wire = self.wire(edge, p0, p3);
+            let value = value_color(evaluator.get(edge.from), width);
+
+            if hovered {
+                wire.draw(thickness, color(HOVER_COLOR), d);
+                self.draw_waypoints(edge, d);
+                wires.insert(edge, wire);
+                continue;
+            }
+
+            // the new value behind the pulse, the old one still ahead of it
+            match pulses.get(&edge.from) {
+                Some(pulse) => {
+                    let t = ((self.time - pulse.start) / PULSE_DURATION).clamp(0.0, 1.0);
+                    let old = value_color(Some(pulse.from), width);
+                    let line = wire.polyline();
+
+                    line.draw_range(0.0, t, thickness, color(value), d);
+                    line.draw_range(t, 1.0, thickness, color(old), d);
+                    if t > 0.0 {
+                        d.draw_circle_v(vector2(line.point(t)), thickness + 2.0, color(value));
+                    }
+                }
+                None => wire.draw(thickness, color(value), d),
+            }
+
+            if self
+                .waypoint_drag
+                .as_ref()
+                .is_some_and(|drag| drag.0 == edge)
+            {
+                self.draw_waypoints(edge, d);
+            }
+
+            wires.insert(edge, wire);
+        }
+        *self.wires.borrow_mut() = wires;
+
+        if let Some((p0, _)) = self.ongoing {
+            let p3 = self.mouse_pos;
+            Bezier { p0, p3 }.draw(2.0, Color::WHITE, d);
+        }
+
+        if let Some(start) = self.rubber_band {
+            let end = self.mouse_pos;
+            let rect = Rectangle::new(
+                start.x.min(end.x),
+                start.y.min(end.y),
+                (start.x - end.x).abs(),
+                (start.y - end.y).abs(),
+            );
+
+            d.draw_rectangle_rec(rect, Color::new(200, 130, 30, 40));
+            d.draw_rectangle_lines_ex(rect, 1.0, Color::new(200, 130, 30, 255));
+        }
+    }
+
+    /// Handles to drag the waypoints of an orthogonal wire by
+    fn draw_waypoints(&self, edge: Edge, d: &mut RaylibDrawHandle) {
+        let Some(route) = self.routes.get(&edge) else {
+            return;
+        };
+        if route.style != WireStyle::Orthogonal {
+            return;
+        }
+
+        let camera = self.camera.get();
+        for &waypoint in &route.waypoints {
+            let center = camera.to_screen(waypoint);
+            let rect = Rectangle::new(
+                center.x - WAYPOINT_SIZE,
+                center.y - WAYPOINT_SIZE,
+                WAYPOINT_SIZE * 2.0,
+                WAYPOINT_SIZE * 2.0,
+            );
+
+            d.draw_rectangle_rec(rect, Color::BLACK);
+            d.draw_rectangle_lines_ex(rect, 1.0, color(HOVER_COLOR));
+        }
+    }
+
+    fn draw_grid(&self, d: &mut RaylibDrawHandle) {
+        let camera = self.camera.get();
+        let spacing = GRID * camera.zoom;
+
+        // too dense to be of any use
+        if spacing < 4.0 {
+            return;
+        }
+
+        let (width, height) = (d.get_screen_width() as f32, d.get_screen_height() as f32);
+        let color = Color::new(40, 40, 40, 255);
+
+        let mut x = camera.offset.x.rem_euclid(spacing);
+        while x < width {
+            d.draw_line_v(Vector2::new(x, 0.0), Vector2::new(x, height), color);
+            x += spacing;
+        }
+
+        let mut y = camera.offset.y.rem_euclid(spacing);
+        while y < height {
+            d.draw_line_v(Vector2::new(0.0, y), Vector2::new(width, y), color);
+            y += spacing;
+        }
+    }
+
+    pub fn draw_imgui(&mut self, d: &mut RaylibDrawHandle) {
+        d.draw_imgui(|ui| {
+            self.draw_menu_bar(ui);
+            self.hovered.set(None);
+            self.node_rects.borrow_mut().clear();
+
+            for (idx, node) in self.nodes.borrow().iter().enumerate() {
+                let old_pos = *node.position.borrow();
+
+                // will update the position if window is moved
+                self.render_node(ui, node, idx, old_pos);
+            }
+
+            self.reposition.set(false);
+            self.update_obstacles();
+
+            self.draw_file_dialog(ui);
+            self.draw_chips_window(ui);
+            self.draw_chip_dialog(ui);
+            self.draw_truth_table(ui);
+            self.draw_expressions(ui);
+            self.draw_synth_dialog(ui);
+            self.draw_minimize(ui);
+            self.draw_equivalence_dialog(ui);
+            self.draw_history(ui);
+
+            ui.window("Simulation")
+                .resizable(false)
+                .always_auto_resize(true)
+                .build(|| {
+                    let simulation = &self.simulation;
+                    let running = simulation.running.get();
+
+                    if ui.button(if running { "Pause" } else { "Run" }) {
+                        simulation.running.set(!running);
+                    }
+
+                    ui.same_line();
+                    if ui.button("Step") {
+                        simulation.step();
+                    }
+
+                    let mut tick_rate = simulation.tick_rate.get();
+                    ui.set_next_item_width(120.0);
+                    if ui.slider("ticks/s", 1.0, 1000.0, &mut tick_rate) {
+                        simulation.tick_rate.set(tick_rate);
+                    }
+
+                    ui.text(format!("tick {}", simulation.tick.get()));
+                });
+
+            if let Some(Vector2 { x, y }) = self.right_click_window.get() {
+                ui.window("right click window")
+                    .title_bar(false)
+                    .resizable(false)
+                    .always_auto_resize(true)
+                    .opened(&mut true)
+                    .position([x, y], ::imgui::Condition::Always)
+                    .collapsed(false, ::imgui::Condition::Always)
+                    .build(|| {
+                        let mut clicked = false;
+
+                        NodeKind::list().into_iter().for_each(|nodekind| {
+                            if ui.button(nodekind.to_string()) {
+                                clicked = true;
+                                let position = self.place(Vec2::new(x, y));
+                                let node = nodekind.build(position, &mut self.ids.borrow_mut());
+                                self.add_node(node);
+                            }
+                        });
+
+                        if !self.chips.borrow().is_empty() {
+                            ui.separator();
+                        }
+
+                        for (idx, chip) in self.chips.borrow().iter().enumerate() {
+                            if ui.button(format!("{}##chip{idx}", chip.name)) {
+                                clicked = true;
+                                let position = self.place(Vec2::new(x, y));
+                                self.add_node(
+                                    NodeKind::Chip(Box::new(chip.clone()))
+                                        .build(position, &mut self.ids.borrow_mut()),
+                                );
+                            }
+                        }
+
+                        if clicked {
+                            self.right_click_window.set(None);
+                        }
+                    });
+            }
+
+            self.typing.set(ui.io().want_text_input);
+            self.over_ui.set(ui.io().want_capture_mouse);
+        });
+    }
+
+    fn add_node(&self, node: Node) {
+        let label = format!("Add {}", node.name);
+        let mut edit = Edit::default();
+        edit.add_nodes(&mut self.nodes.borrow_mut(), [node]);
+        self.commit(label, edit);
+    }
+
+    fn draw_menu_bar(&self, ui: &::imgui::Ui) {
+        ui.main_menu_bar(|| {
+            ui.menu("File", || {
+                if ui.menu_item("New") {
+                    *self.file_action.borrow_mut() = Some(FileAction::New);
+                }
+
+                if ui.menu_item_config("Open...").shortcut("Ctrl+O").build() {
+                    self.open_file_dialog(FileDialog::Open);
+                }
+
+                if ui.menu_item_config("Save").shortcut("Ctrl+S").build() {
+                    self.save_current();
+                }
+
+                if ui.menu_item("Save As...") {
+                    self.open_file_dialog(FileDialog::SaveAs);
+                }
+            });
+
+            ui.menu("View", || {
+                let mut snap = self.snap.get();
+                if ui
+                    .menu_item_config("Snap to grid")
+                    .build_with_ref(&mut snap)
+                {
+                    self.snap.set(snap);
+                }
+
+                let mut orthogonal = self.wire_style.get() == WireStyle::Orthogonal;
+                if ui
+                    .menu_item_config("Orthogonal wires")
+                    .shortcut("R on a wire")
+                    .build_with_ref(&mut orthogonal)
+                {
+                    self.wire_style.set(match orthogonal {
+                        true => WireStyle::Orthogonal,
+                        false => WireStyle::Curved,
+                    });
+                }
+
+                let mut animate = self.animate.get();
+                if ui
+                    .menu_item_config("Animate signals")
+                    .build_with_ref(&mut animate)
+                {
+                    self.animate.set(animate);
+                    self.pulses.borrow_mut().clear();
+                }
+
+                if ui.menu_item("Reset view") {
+                    self.camera.set(Camera::default());
+                    self.reposition.set(true);
+                }
+            });
+
+            ui.menu("Edit", || {
+                let history = self.history.borrow();
+
+                if ui
+                    .menu_item_config("Undo")
+                    .shortcut("Ctrl+Z")
+                    .enabled(!history.done.is_empty())
+                    .build()
+                {
+                    self.history_action.set(Some(HistoryAction::Undo));
+                }
+
+                if ui
+                    .menu_item_config("Redo")
+                    .shortcut("Ctrl+Shift+Z")
+                    .enabled(!history.undone.is_empty())
+                    .build()
+                {
+                    self.history_action.set(Some(HistoryAction::Redo));
+                }
+
+                ui.separator();
+                let selected = self.selected.borrow().iter().copied().collect::<Vec<_>>();
+
+                if ui
+                    .menu_item_config("Copy")
+                    .shortcut("Ctrl+C")
+                    .enabled(!selected.is_empty())
+                    .build()
+                {
+                    self.clipboard_action.set(Some(ClipboardAction::Copy));
+                }
+
+                if ui.menu_item_config("Paste").shortcut("Ctrl+V").build() {
+                    // the mouse is on the menu, paste in the middle of the screen
+                    let [width, height] = ui.io().display_size;
+                    let center = Vector2::new(width / 2.0, height / 2.0);
+                    self.clipboard_action
+                        .set(Some(ClipboardAction::Paste(center)));
+                }
+
+                if ui.menu_item_config("Select all").shortcut("Ctrl+A").build() {
+                    *self.selected.borrow_mut() =
+                        self.nodes.borrow().iter().map(|node| node.id()).collect();
+                }
+
+                if ui
+                    .menu_item_config("Duplicate")
+                    .shortcut("Ctrl+D")
+                    .enabled(!selected.is_empty())
+                    .build()
+                {
+                    *self.node_action.borrow_mut() = Some(NodeAction::Duplicate(selected.clone()));
+                }
+
+                if ui
+                    .menu_item_config("Delete")
+                    .shortcut("Del")
+                    .enabled(!selected.is_empty())
+                    .build()
+                {
+                    *self.node_action.borrow_mut() = Some(NodeAction::Delete(selected));
+                }
+
+                ui.separator();
+                let mut show = self.show_history.get();
+                if ui.menu_item_config("History").build_with_ref(&mut show) {
+                    self.show_history.set(show);
+                }
+            });
+
+            ui.menu("Analysis", || {
+                if ui.menu_item("Truth table") {
+                    self.show_truth_table();
+                }
+
+                if ui.menu_item("Expressions") {
+                    self.show_expressions();
+                }
+
+                if ui.menu_item("Minimize") {
+                    self.show_minimize();
+                }
+
+                if ui.menu_item("Check equivalence...") {
+                    self.equivalence
+                        .borrow_mut()
+                        .get_or_insert_with(|| EquivalenceDialog {
+                            sides: [
+                                EquivalenceSide::Canvas,
+                                EquivalenceSide::File("other.json".to_string()),
+                            ],
+                            result: None,
+                        });
+                }
+
+                if ui.menu_item("Synthesize...") {
+                    self.synth_dialog
+                        .borrow_mut()
+                        .get_or_insert_with(SynthDialog::default);
+                }
+            });
+
+            if let Some(status) = &*self.status.borrow() {
+                ui.separator();
+                ui.text(status);
+            }
+        });
+    }
+
+    fn draw_file_dialog(&self, ui: &::imgui::Ui) {
+        let mut close = false;
+
+        if let Some((dialog, path)) = &mut *self.file_dialog.borrow_mut() {
+            let title = match dialog {
+                FileDialog::Open => "Open circuit",
+                FileDialog::SaveAs => "Save circuit as",
+            };
+
+            ui.window(title)
+                .resizable(false)
+                .collapsible(false)
+                .always_auto_resize(true)
+                .build(|| {
+                    ui.set_next_item_width(300.0);
+                    let entered = ui.input_text("path", path).enter_returns_true(true).build();
+
+                    if entered || ui.button("OK") {
+                        let path = PathBuf::from(&*path);
+                        *self.file_action.borrow_mut() = Some(match dialog {
+                            FileDialog::Open => FileAction::Open(path),
+                            FileDialog::SaveAs => FileAction::Save(path),
+                        });
+                        close = true;
+                    }
+
+                    ui.same_line();
+                    if ui.button("Cancel") {
+                        close = true;
+                    }
+                });
+        }
+
+        if close {
+            *self.file_dialog.borrow_mut() = None;
+        }
+    }
+
+    fn show_truth_table(&self) {
+        let nodes = self.nodes.borrow();
+        let table = TruthTable::new(&nodes, nodes.dependency_graph(), self.simulation.tick.get());
+
+        let path = self
+            .truth_table
+            .borrow_mut()
+            .take()
+            .map(|view| view.path)
+            .unwrap_or_else(|| "truth_table.csv".to_string());
+
+        *self.truth_table.borrow_mut() = Some(TruthTableView {
+            order: (0..table.rows.len()).collect(),
+            table,
+            path,
+        });
+    }
+
+    fn draw_truth_table(&self, ui: &::imgui::Ui) {
+        let mut open = true;
+        let mut refresh = false;
+
+        if let Some(view) = &mut *self.truth_table.borrow_mut() {
+            ui.window("Truth table")
+                .opened(&mut open)
+                .size([400.0, 300.0], ::imgui::Condition::FirstUseEver)
+                .build(|| {
+                    let table = &view.table;
+
+                    if table.is_truncated() {
+                        ui.text_colored(
+                            X_COLOR,
+                            format!(
+                                "{} input bits, only the first {} of 2^{} rows are shown",
+                                table.input_bits,
+                                table.rows.len(),
+                                table.input_bits
+                            ),
+                        );
+                    }
+
+                    refresh = ui.button("Refresh");
+
+                    ui.same_line();
+                    if ui.button("Copy CSV") {
+                        ui.set_clipboard_text(table.to_csv());
+                    }
+
+                    ui.same_line();
+                    if ui.button("Copy Markdown") {
+                        ui.set_clipboard_text(table.to_markdown());
+                    }
+
+                    ui.set_next_item_width(200.0);
+                    ui.input_text("##path", &mut view.path).build();
+                    ui.same_line();
+                    if ui.button("Export") {
+                        let text = match view.path.ends_with(".md") {
+                            true => table.to_markdown(),
+                            false => table.to_csv(),
+                        };
+
+                        *self.status.borrow_mut() = Some(match std::fs::write(&view.path, text) {
+                            Ok(()) => format!("exported the truth table to {}", view.path),
+                            Err(err) => format!("failed to export {}: {err}", view.path),
+                        });
+                    }
+
+                    let columns = table.columns().count();
+                    if columns == 0 {
+                        ui.text("the circuit has no inputs or displays");
+                        return;
+                    }
+
+                    let flags = ::imgui::TableFlags::SORTABLE
+                        | ::imgui::TableFlags::BORDERS
+                        | ::imgui::TableFlags::ROW_BG
+                        | ::imgui::TableFlags::SCROLL_Y;
+
+                    let Some(_table) = ui.begin_table_with_flags("##table", columns, flags) else {
+                        return;
+                    };
+
+                    for (idx, column) in table.columns().enumerate() {
+                        ui.table_setup_column(format!("{}##{idx}", column.name));
+                    }
+                    ui.table_setup_scroll_freeze(0, 1);
+                    ui.table_headers_row();
+
+                    if let Some(specs) = ui.table_sort_specs_mut() {
+                        specs.conditional_sort(|specs| {
+                            let Some(spec) = specs.iter().next() else {
+                                return;
+                            };
+
+                            // hex digits sort like numbers, X and Z after them
+                            let column = spec.column_idx();
+                            let width = table.columns().nth(column).unwrap().width;
+                            view.order
+                                .sort_by_cached_key(|&row| table.rows[row][column].format(width));
+
+                            if let Some(::imgui::TableSortDirection::Descending) =
+                                spec.sort_direction()
+                            {
+                                view.order.reverse();
+                            }
+                        });
+                    }
+
+                    let clipper = ::imgui::ListClipper::new(view.order.len() as i32).begin(ui);
+                    for idx in clipper.iter() {
+                        ui.table_next_row();
+                        for cell in table.cells(view.order[idx as usize]) {
+                            ui.table_next_column();
+                            ui.text(cell);
+                        }
+                    }
+                });
+        }
+
+        if !open {
+            *self.truth_table.borrow_mut() = None;
+        } else if refresh {
+            self.show_truth_table();
+        }
+    }
+
+    fn show_expressions(&self) {
+        let nodes = self.nodes.borrow();
+
+        let expressions = nodes
+            .iter()
+            .filter(|node| matches!(node.kind, NodeKind::Display(_)))
+            .map(|node| {
+                let forms =
+                    expr::extract(&nodes, nodes.dependency_graph(), node.id()).map(|expr| {
+                        let mut forms = vec![("raw", expr.to_string())];
+
+                        // minimizing is exponential in the number of inputs
+                        let vars = expr.variables();
+                        if vars.len() <= MAX_VARIABLES {
+                            let minterms = expr.minterms(&vars);
+                            forms.push(("SOP", minimize::sop(&vars, &minterms).to_string()));
+                            forms.push(("POS", minimize::pos(&vars, &minterms).to_string()));
+                        }
+
+                        forms
+                    });
+
+                Expression {
+                    display: node.label(&nodes),
+                    forms,
+                }
+            })
+            .collect();
+
+        *self.expressions.borrow_mut() = Some(expressions);
+    }
+
+    fn show_minimize(&self) {
+        let nodes = self.nodes.borrow();
+        let subnetwork = match Subnetwork::new(&nodes, &self.selected.borrow()) {
+            Ok(subnetwork) => subnetwork,
+            Err(err) => {
+                *self.status.borrow_mut() = Some(format!("can't minimize: {err}"));
+                return;
+            }
+        };
+
+        if subnetwork.inputs.len() > MAX_VARIABLES {
+            *self.status.borrow_mut() = Some(format!(
+                "can't minimize: {} inputs, at most {MAX_VARIABLES} are supported",
+                subnetwork.inputs.len()
+            ));
+            return;
+        }
+
+        let table = subnetwork.truth_table(&nodes);
+        let vars = table
+            .inputs
+            .iter()
+            .map(|column| column.name.clone())
+            .collect::<Vec<_>>();
+
+        let outputs = table
+            .outputs
+            .iter()
+            .enumerate()
+            .map(|(idx, column)| {
+                let mut minterms = vec![];
+                let mut dont_cares = vec![];
+                for (row, values) in table.rows.iter().enumerate() {
+                    match values[vars.len() + idx].to_bool() {
+                        Some(true) => minterms.push(row as u32),
+                        Some(false) => {}
+                        None => dont_cares.push(row as u32),
+                    }
+                }
+
+                let primes = minimize::primes(&minterms, &dont_cares, vars.len());
+                let cover = minimize::minimize(&minterms, &dont_cares, vars.len());
+
+                let maxterms = minimize::maxterms(&minterms, &dont_cares, vars.len());
+                let pos = minimize::minimize(&maxterms, &dont_cares, vars.len());
+
+                MinimizedOutput {
+                    name: column.name.clone(),
+                    sop: minimize::sum_of_products(&cover, &vars).to_string(),
+                    pos: minimize::product_of_sums(&pos, &vars).to_string(),
+                    minterms,
+                    dont_cares,
+                    primes,
+                    cover,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let mut view = self.minimize.borrow_mut();
+        let nand_only = view.as_ref().is_some_and(|view| view.nand_only);
+        let output = view.as_ref().map_or(0, |view| view.output);
+
+        *view = Some(MinimizeView {
+            output: output.min(outputs.len().saturating_sub(1)),
+            subnetwork,
+            vars,
+            outputs,
+            hovered: None,
+            nand_only,
+        });
+    }
+
+    fn draw_minimize(&self, ui: &::imgui::Ui) {
+        let mut open = true;
+        let mut refresh = false;
+
+        if let Some(view) = &mut *self.minimize.borrow_mut() {
+            ui.window("Minimize")
+                .opened(&mut open)
+                .size([500.0, 400.0], ::imgui::Condition::FirstUseEver)
+                .build(|| {
+                    refresh = ui.button("Refresh");
+
+                    ui.same_line();
+                    if ui.button("Replace gates") {
+                        self.replace_minimized.set(true);
+                    }
+                    if ui.is_item_hovered() {
+                        ui.tooltip_text("swap the gates for the minimal sums of products");
+                    }
+
+                    ui.same_line();
+                    ui.checkbox("NAND gates only", &mut view.nand_only);
+
+                    ui.text(format!(
+                        "{} gates, inputs {}",
+                        view.subnetwork.nodes.len(),
+                        view.vars.join(", ")
+                    ));
+
+                    let names = view.outputs.iter().map(|output| &output.name);
+                    let names = names.collect::<Vec<_>>();
+                    ui.set_next_item_width(200.0);
+                    ui.combo_simple_string("output", &mut view.output, &names);
+
+                    let output = &view.outputs[view.output];
+                    ui.text(format!("SOP {}", output.sop));
+                    ui.text(format!("POS {}", output.pos));
+
+                    if !output.dont_cares.is_empty() {
+                        ui.text_disabled("- marks rows where the output is X or Z");
+                    }
+
+                    ui.separator();
+                    match view.vars.len() {
+                        0 => {}
+                        vars if vars <= MAX_MAP_VARIABLES => {
+                            self.draw_karnaugh_map(ui, &view.vars, output, view.hovered)
+                        }
+                        vars => ui.text_disabled(format!(
+                            "Karnaugh maps go up to {MAX_MAP_VARIABLES} inputs, this has {vars}"
+                        )),
+                    }
+
+                    ui.separator();
+                    ui.text(format!("prime implicants ({} chosen)", output.cover.len()));
+
+                    let essential = minimize::essential(&output.primes, &output.minterms);
+                    view.hovered = None;
+
+                    for prime in &output.primes {
+                        let mut text = format!(
+                            "{}  {}",
+                            prime.pattern(view.vars.len()),
+                            prime.product(&view.vars)
+                        );
+                        if essential.contains(prime) {
+                            text += "  (essential)";
+                        }
+
+                        match output.cover.iter().position(|term| term == prime) {
+                            Some(idx) => {
+                                let [r, g, b, _] = GROUP_COLORS[idx % GROUP_COLORS.len()];
+                                ui.text_colored([r, g, b, 1.0], text);
+                            }
+                            None => ui.text_disabled(text),
+                        }
+
+                        if ui.is_item_hovered() {
+                            view.hovered = Some(*prime);
+                        }
+                    }
+                });
+        }
+
+        if !open {
+            *self.minimize.borrow_mut() = None;
+        } else if refresh {
+            self.show_minimize();
+        }
+    }
+
+    /// Rows and columns are labelled with the levels of their variables, cells are colored by
+    /// the chosen implicant covering them
+    fn draw_karnaugh_map(
+        &self,
+        ui: &::imgui::Ui,
+        vars: &[String],
+        output: &MinimizedOutput,
+        hovered: Option<Implicant>,
+    ) {
+        let map = minimize::karnaugh_map(vars.len());
+        let (row_vars, column_vars) = vars.split_at(vars.len() / 2);
+        let code = |code: u32, bits: usize| match bits {
+            0 => String::new(),
+            _ => format!("{code:0bits$b}"),
+        };
+
+        let flags = ::imgui::TableFlags::BORDERS | ::imgui::TableFlags::SIZING_FIXED_FIT;
+        let Some(_table) = ui.begin_table_with_flags("##map", map[0].len() + 1, flags) else {
+            return;
+        };
+
+        ui.table_setup_column(format!("{}\\{}", row_vars.join(""), column_vars.join("")));
+        for column in minimize::gray_code(column_vars.len()) {
+            ui.table_setup_column(code(column, column_vars.len()));
+        }
+        ui.table_headers_row();
+
+        for (row, cells) in minimize::gray_code(row_vars.len()).into_iter().zip(&map) {
+            ui.table_next_row();
+            ui.table_next_column();
+            ui.text(code(row, row_vars.len()));
+
+            for &cell in cells {
+                ui.table_next_column();
+
+                let group = output.cover.iter().position(|term| term.covers(cell));
+                let color = match hovered {
+                    Some(hovered) if hovered.covers(cell) => Some([1.0, 1.0, 1.0, 0.5]),
+                    _ if output.minterms.contains(&cell) => {
+                        group.map(|idx| GROUP_COLORS[idx % GROUP_COLORS.len()])
+                    }
+                    _ => None,
+                };
+
+                if let Some(color) = color {
+                    ui.table_set_bg_color(::imgui::TableBgTarget::CELL_BG, color);
+                }
+
+                ui.text(match () {
+                    _ if output.minterms.contains(&cell) => "1",
+                    _ if output.dont_cares.contains(&cell) => "-",
+                    _ => "0",
+                });
+            }
+        }
+    }
+
+    /// The selected nodes and the wires between them
+    fn copy_selection(&self) -> (Vec<Node>, Vec<Edge>) {
+        let selected = self.selected.borrow();
+        let nodes = self.nodes.borrow();
+        let edges = nodes.edges().iter().copied();
+
+        (
+            nodes
+                .iter()
+                .filter(|node| selected.contains(&node.id()))
+                .cloned()
+                .collect(),
+            edges
+                .filter(|edge| {
+                    selected.contains(&edge.from.node_id) && selected.contains(&edge.to.node_id)
+                })
+                .collect(),
+        )
+    }
+
+    fn check_equivalence(&self, sides: &[EquivalenceSide; 2]) -> Result<Report, String> {
+        let circuits = sides
+            .iter()
+            .map(|side| match side {
+                EquivalenceSide::Canvas => {
+                    let nodes = self.nodes.borrow();
+                    Ok((nodes.to_vec(), nodes.edges().to_vec()))
+                }
+                EquivalenceSide::File(path) => CircuitFile::load(Path::new(path))
+                    .and_then(CircuitFile::into_circuit)
+                    .map(|contents| (contents.nodes, contents.edges))
+                    .map_err(|err| format!("failed to open {path}: {err}")),
+                EquivalenceSide::Selection(nodes, edges) => Ok((nodes.clone(), edges.clone())),
+            })
+            .collect::<Result<Vec<(Vec<Node>, Vec<Edge>)>, _>>()?;
+
+        let [(a, a_edges), (b, b_edges)] = <[_; 2]>::try_from(circuits).unwrap();
+        equivalence::check(
+            &a,
+            &eval::build_dependency_graph(a_edges),
+            &b,
+            &eval::build_dependency_graph(b_edges),
+        )
+    }
+
+    fn draw_equivalence_dialog(&self, ui: &::imgui::Ui) {
+        let mut open = true;
+
+        if let Some(dialog) = &mut *self.equivalence.borrow_mut() {
+            ui.window("Check equivalence")
+                .opened(&mut open)
+                .resizable(false)
+                .always_auto_resize(true)
+                .build(|| {
+                    ui.text("inputs and displays are matched by name");
+
+                    for (idx, side) in dialog.sides.iter_mut().enumerate() {
+                        ui.separator();
+                        ui.text(["first circuit", "second circuit"][idx]);
+
+                        if ui.radio_button_bool(
+                            format!("canvas##{idx}"),
+                            matches!(side, EquivalenceSide::Canvas),
+                        ) {
+                            *side = EquivalenceSide::Canvas;
+                        }
+
+                        ui.same_line();
+                        if ui.radio_button_bool(
+                            format!("file##{idx}"),
+                            matches!(side, EquivalenceSide::File(_)),
+                        ) && !matches!(side, EquivalenceSide::File(_))
+                        {
+                            *side = EquivalenceSide::File("circuit.json".to_string());
+                        }
+
+                        ui.same_line();
+                        if ui.radio_button_bool(
+                            format!("selection##{idx}"),
+                            matches!(side, EquivalenceSide::Selection(..)),
+                        ) {
+                            let (nodes, edges) = self.copy_selection();
+                            *side = EquivalenceSide::Selection(nodes, edges);
+                        }
+
+                        match side {
+                            EquivalenceSide::Canvas => {}
+                            EquivalenceSide::File(path) => {
+                                ui.set_next_item_width(300.0);
+                                ui.input_text(format!("path##{idx}"), path).build();
+                            }
+                            EquivalenceSide::Selection(nodes, _) => {
+                                ui.text(format!("{} nodes", nodes.len()));
+                                ui.same_line();
+                                if ui.small_button(format!("Use current selection##{idx}")) {
+                                    let (nodes, edges) = self.copy_selection();
+                                    *side = EquivalenceSide::Selection(nodes, edges);
+                                }
+                            }
+                        }
+                    }
+
+                    ui.separator();
+                    if ui.button("Check") {
+                        dialog.result = Some(self.check_equivalence(&dialog.sides));
+                    }
+
+                    let report = match &dialog.result {
+                        None => return,
+                        Some(Err(err)) => {
+                            ui.text_colored(X_COLOR, err);
+                            return;
+                        }
+                        Some(Ok(report)) => report,
+                    };
+
+                    let method = match report.method {
+                        Method::Exhaustive => "every input combination was simulated",
+                        Method::Sat => "proved with SAT",
+                    };
+
+                    let Some(counterexample) = &report.counterexample else {
+                        ui.text_colored([0.3, 0.9, 0.3, 1.0], format!("equivalent, {method}"));
+                        return;
+                    };
+
+                    ui.text_colored(X_COLOR, "not equivalent, for example with");
+                    for input in &counterexample.inputs {
+                        ui.text(format!(
+                            "  {} = {}",
+                            input.name,
+                            input.value.format(input.width)
+                        ));
+                    }
+
+                    ui.text("the displays differ");
+                    for (output, other) in &counterexample.outputs {
+                        ui.text(format!(
+                            "  {} = {} vs {}",
+                            output.name,
+                            output.value.format(output.width),
+                            other.format(output.width)
+                        ));
+                    }
+                });
+        }
+
+        if !open {
+            *self.equivalence.borrow_mut() = None;
+        }
+    }
+
+    fn draw_expressions(&self, ui: &::imgui::Ui) {
+        let mut open = true;
+        let mut refresh = false;
+
+        if let Some(expressions) = &*self.expressions.borrow() {
+            ui.window("Expressions")
+                .opened(&mut open)
+                .size([400.0, 300.0], ::imgui::Condition::FirstUseEver)
+                .build(|| {
+                    refresh = ui.button("Refresh");
+
+                    if expressions.is_empty() {
+                        ui.text("the circuit has no displays");
+                    }
+
+                    for (idx, expression) in expressions.iter().enumerate() {
+                        ui.separator();
+                        ui.text(&expression.display);
+
+                        let forms = match &expression.forms {
+                            Ok(forms) => forms,
+                            Err(err) => {
+                                ui.text_colored(X_COLOR, err);
+                                continue;
+                            }
+                        };
+
+                        if forms.len() == 1 {
+                            ui.text_disabled("too many inputs to simplify");
+                        }
+
+                        for (label, form) in forms {
+                            ui.text(format!("{label:>4} {form}"));
+                            ui.same_line();
+                            if ui.small_button(format!("Copy##{label}{idx}")) {
+                                ui.set_clipboard_text(form);
+                            }
+                        }
+                    }
+                });
+        }
+
+        if !open {
+            *self.expressions.borrow_mut() = None;
+        } else if refresh {
+            self.show_expressions();
+        }
+    }
+
+    fn draw_history(&self, ui: &::imgui::Ui) {
+        if !self.show_history.get() {
+            return;
+        }
 
-        if rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_RIGHT) {
-            // remove the wire if right clicked on the pin
-            if let Some((node, _)) = self.get_node_and_pin(self.mouse_pos) {
-                let id = node.id;
-                if let Some((idx, _)) = self.edges.iter().enumerate().find(|(_, i)| {
-                    let Edge { from, to } = (*i).borrow().0;
-                    from.node_id == id || to.node_id == id
-                }) {
-                    self.edges.swap_remove(idx);
+        let mut open = true;
+        ui.window("History")
+            .opened(&mut open)
+            .size([250.0, 300.0], ::imgui::Condition::FirstUseEver)
+            .build(|| {
+                let history = self.history.borrow();
+                let done = history.done.len();
+
+                if ui.selectable_config("(start)").selected(done == 0).build() {
+                    self.history_action.set(Some(HistoryAction::Jump(0)));
                 }
-            } else {
-                self.right_click_window
-                    .set(match self.right_click_window.get() {
-                        None => Some(self.mouse_pos),
-                        _ => None,
+
+                // undone entries are greyed out, clicking one redoes up to it
+                let entries = history.done.iter().chain(history.undone.iter().rev());
+                for (idx, entry) in entries.enumerate() {
+                    let _color = (idx >= done).then(|| {
+                        ui.push_style_color(::imgui::StyleColor::Text, [0.5, 0.5, 0.5, 1.0])
                     });
-            }
-        }
 
-        // Wire start
-        if self.ongoing.is_none() && rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_LEFT) {
-            if let Some((node, socket)) = self.get_node_and_pin(self.mouse_pos) {
-                if !matches!(socket.borrow().kind, SocketKind::Output) {
-                    return;
+                    let label = format!("{}##{idx}", entry.label);
+                    if ui
+                        .selectable_config(label)
+                        .selected(idx + 1 == done)
+                        .build()
+                    {
+                        self.history_action.set(Some(HistoryAction::Jump(idx + 1)));
+                    }
                 }
+            });
 
-                let ongoing = Some((
-                    socket.borrow().absolute_position.unwrap(),
-                    SocketRef {
-                        node_id: node.id,
-                        socket_id: socket.borrow().id,
-                    },
-                ));
-
-                self.ongoing = ongoing;
-            }
+        if !open {
+            self.show_history.set(false);
         }
-        // Wire end
-        else if self.ongoing.is_some()
-            && rl.is_mouse_button_released(MouseButton::MOUSE_BUTTON_LEFT)
-        {
-            let to = *self.ongoing.unwrap().1.borrow();
-            if let Some((node, socket)) = self.get_node_and_pin(self.mouse_pos) {
-                if !matches!(socket.borrow().kind, SocketKind::Input) {
-                    return;
-                }
+    }
 
-                let b = socket.borrow();
-                let edge = Edge {
-                    from: SocketRef {
-                        node_id: node.id,
-                        socket_id: b.id,
-                    },
-                    to,
-                };
+    fn draw_chips_window(&self, ui: &::imgui::Ui) {
+        ui.window("Chips")
+            .resizable(false)
+            .always_auto_resize(true)
+            .build(|| {
+                if let Some(editing) = self.editing.last() {
+                    let name = &self.chips.borrow()[editing.chip].name;
+                    ui.text(format!("editing {name}"));
 
-                let v2 = b.absolute_position.unwrap();
-                drop(b);
-                let v1 = self.ongoing.unwrap().0;
+                    if ui.button("Save chip") {
+                        *self.chip_action.borrow_mut() = Some(ChipAction::Finish { save: true });
+                    }
 
-                if !self.edges.iter().any(|i| (*i).borrow().0 == edge) {
-                    self.edges.push((edge, v1, v2).into());
+                    ui.same_line();
+                    if ui.button("Discard") {
+                        *self.chip_action.borrow_mut() = Some(ChipAction::Finish { save: false });
+                    }
+
+                    ui.separator();
                 }
 
-                let dep_graph = build_dependency_graph(self);
-                self.dependency_graph = dep_graph;
+                let selected = self.selected.borrow();
+                ui.text(format!(
+                    "{} selected (shift + click or drag a box)",
+                    selected.len()
+                ));
 
-                self.eval.set(true);
-            }
+                if ui.button("Create chip from selection") && !selected.is_empty() {
+                    let exposed = self
+                        .nodes
+                        .borrow()
+                        .iter()
+                        .filter(|node| {
+                            selected.contains(&node.id())
+                                && matches!(node.kind, NodeKind::Input(_) | NodeKind::Display(_))
+                        })
+                        .map(|node| node.id())
+                        .collect();
 
-            self.ongoing = None;
-        }
+                    *self.chip_dialog.borrow_mut() = Some(ChipDialog {
+                        name: "CHIP".to_string(),
+                        exposed,
+                        replace: true,
+                    });
+                }
+
+                for (idx, chip) in self.chips.borrow().iter().enumerate() {
+                    ui.text(&chip.name);
+                    ui.same_line();
+                    if ui.button(format!("Edit##chip{idx}")) {
+                        *self.chip_action.borrow_mut() = Some(ChipAction::Edit(idx));
+                    }
+                }
+            });
     }
 
-    /// last item is the location of center for snapping
-    fn get_node_and_pin(&mut self, point: Vector2) -> Option<(&Node, &RefCell<Socket>)> {
-        // * 2 for snapping
-        let pred = |i: Vector2| (i - point).length_sqr() <= (PIN_RADIUS).powi(2);
+    fn draw_chip_dialog(&self, ui: &::imgui::Ui) {
+        let mut close = false;
 
-        self.nodes.get_mut().iter().find_map(
-            |node @ Node {
-                 inputs, outputs, ..
-             }| {
-                inputs
-                    .iter()
-                    .find(|i| (*i).borrow().absolute_position.map(pred).unwrap_or(false))
-                    .or_else(|| {
-                        outputs
-                            .iter()
-                            .find(|o| (*o).borrow().absolute_position.map(pred).unwrap_or(false))
-                    })
-                    .map(|i| (node, i))
-            },
-        )
-    }
+        if let Some(dialog) = &mut *self.chip_dialog.borrow_mut() {
+            ui.window("Create chip")
+                .resizable(false)
+                .collapsible(false)
+                .always_auto_resize(true)
+                .build(|| {
+                    ui.set_next_item_width(200.0);
+                    ui.input_text("name", &mut dialog.name).build();
 
-    pub fn draw(&self, d: &mut RaylibDrawHandle) {
-        for i in self.edges.iter() {
-            let (_, p0, p3) = *i.borrow();
-            Bezier { p0, p3 }.draw(2.0, Color::WHITE, d);
+                    ui.text("pins");
+                    let selected = self.selected.borrow();
+                    for node in self.nodes.borrow().iter() {
+                        if !selected.contains(&node.id())
+                            || !matches!(node.kind, NodeKind::Input(_) | NodeKind::Display(_))
+                        {
+                            continue;
+                        }
+
+                        let mut exposed = dialog.exposed.contains(&node.id());
+                        let label = format!("{} ({})##pin{}", node.name, node.kind, node.id());
+                        if ui.checkbox(label, &mut exposed) {
+                            match exposed {
+                                true => dialog.exposed.insert(node.id()),
+                                false => dialog.exposed.remove(&node.id()),
+                            };
+                        }
+                    }
+
+                    ui.checkbox("replace selection", &mut dialog.replace);
+
+                    if ui.button("Create") && !dialog.name.trim().is_empty() {
+                        *self.chip_action.borrow_mut() = Some(ChipAction::Create(dialog.clone()));
+                        close = true;
+                    }
+
+                    ui.same_line();
+                    if ui.button("Cancel") {
+                        close = true;
+                    }
+                });
         }
 
-        if let Some((p0, _)) = self.ongoing {
-            let p3 = self.mouse_pos;
-            Bezier { p0, p3 }.draw(2.0, Color::WHITE, d);
+        if close {
+            *self.chip_dialog.borrow_mut() = None;
         }
     }
 
-    pub fn draw_imgui(&mut self, d: &mut RaylibDrawHandle) {
-        d.draw_imgui(|ui| {
-            for (idx, node) in self.nodes.borrow().iter().enumerate() {
-                let old_pos = *node.position.borrow();
+    fn draw_synth_dialog(&self, ui: &::imgui::Ui) {
+        let mut close = false;
 
-                // will update old_pos if window is moved
-                self.render_node(ui, node, idx, old_pos);
+        if let Some(dialog) = &mut *self.synth_dialog.borrow_mut() {
+            ui.window("Synthesize")
+                .resizable(false)
+                .collapsible(false)
+                .always_auto_resize(true)
+                .build(|| {
+                    ui.text("one output per line, e.g. out = (a & b) | !c");
+                    ui.input_text_multiline("##equations", &mut dialog.text, [400.0, 120.0])
+                        .build();
+                    ui.checkbox("NAND gates only", &mut dialog.nand_only);
+
+                    if let Some(error) = &dialog.error {
+                        ui.text_colored(X_COLOR, error);
+                    }
 
-                let new_pos = *node.position.borrow();
-                let node_id = node.id;
-
-                if new_pos != old_pos {
-                    let displacement = new_pos - old_pos;
-                    self.edges.iter().for_each(|i| {
-                        let mut b = i.borrow_mut();
-                        let Edge {
-                            from:
-                                SocketRef {
-                                    node_id: node_id2, ..
-                                },
-                            to:
-                                SocketRef {
-                                    node_id: node_id1, ..
-                                },
-                        } = b.0;
-
-                        if node_id1 == node_id {
-                            b.1 += displacement;
-                        } else if node_id2 == node_id {
-                            b.2 += displacement;
+                    if ui.button("Create") {
+                        match expr::parse_equations(&dialog.text) {
+                            Ok(equations) if equations.is_empty() => {
+                                dialog.error = Some("nothing to synthesize".to_string());
+                            }
+                            Ok(equations) => {
+                                *self.synthesis.borrow_mut() = Some(Synthesis {
+                                    equations,
+                                    nand_only: dialog.nand_only,
+                                });
+                                close = true;
+                            }
+                            Err(err) => dialog.error = Some(err),
                         }
-                    });
-                };
-            }
+                    }
 
-            if let Some(node_position @ Vector2 { x, y }) = self.right_click_window.get() {
-                ui.window("right click window")
-                    .title_bar(false)
-                    .resizable(false)
-                    .always_auto_resize(true)
-                    .opened(&mut true)
-                    .position([x, y], ::imgui::Condition::Always)
-                    .collapsed(false, ::imgui::Condition::Always)
-                    .build(|| {
-                        let mut clicked = false;
+                    ui.same_line();
+                    if ui.button("Cancel") {
+                        close = true;
+                    }
+                });
+        }
 
-                        NodeKind::list().into_iter().for_each(|nodekind| {
-                            if ui.button(nodekind.to_string()) {
-                                clicked = true;
-                                self.nodes
-                                    .borrow_mut()
-                                    .push(nodekind.build(node_position, id_salt));
-                            }
-                        });
+        if close {
+            *self.synth_dialog.borrow_mut() = None;
+        }
+    }
 
-                        if clicked {
-                            self.right_click_window.set(None);
-                        }
-                    });
-            }
+    fn render_node(&self, ui: &mut ::imgui::Ui, node: &Node, idx: usize, old_pos: Vec2) {
+        let condition = match self.reposition.get() {
+            true => ::imgui::Condition::Always,
+            false => ::imgui::Condition::Appearing,
+        };
+
+        // highlight selected nodes through their title bar
+        let selected = self.selected.borrow().contains(&node.id());
+        let _title_colors = selected.then(|| {
+            let color = [0.8, 0.5, 0.1, 1.0];
+            (
+                ui.push_style_color(::imgui::StyleColor::TitleBg, color),
+                ui.push_style_color(::imgui::StyleColor::TitleBgActive, color),
+            )
         });
-    }
 
-    fn render_node(&self, ui: &mut ::imgui::Ui, node: &Node, idx: usize, old_pos: Vector2) {
-        ui.window(&format!("{}  #{idx}", node.name))
+        // spacing shrinks and grows with the text
+        let camera = self.camera.get();
+        let style = ui.clone_style();
+        let scaled = |[x, y]: [f32; 2]| [x * camera.zoom, y * camera.zoom];
+        let _style = (
+            ui.push_style_var(::imgui::StyleVar::WindowPadding(scaled(
+                style.window_padding,
+            ))),
+            ui.push_style_var(::imgui::StyleVar::FramePadding(scaled(style.frame_padding))),
+            ui.push_style_var(::imgui::StyleVar::ItemSpacing(scaled(style.item_spacing))),
+        );
+
+        let screen = camera.to_screen(old_pos);
+
+        // the part after ### is the window id, titles can repeat
+        ui.window(format!("{}  #{idx}###node{}", node.name, node.id()))
             .resizable(false)
             .collapsible(false)
-            .position([old_pos.x, old_pos.y], ::imgui::Condition::Appearing)
+            .position([screen.x, screen.y], condition)
             .always_auto_resize(true)
             .build(|| {
+                ui.set_window_font_scale(camera.zoom);
+
+                // imgui keeps window positions on whole pixels, smaller differences are rounding
                 let [x, y] = ui.window_pos();
-                *node.position.borrow_mut() = Vector2::new(x, y);
+                let moved = (Vec2::new(x, y) - screen).abs().max_element() >= 1.0;
+                let position = match moved {
+                    true => camera.to_canvas(Vec2::new(x, y)),
+                    false => old_pos,
+                };
+
+                // recorded and passed on to the rest of the selection by `handle_drag`
+                if !self.reposition.get() && moved {
+                    self.drag.set(Some((node.id(), old_pos)));
+                }
+                *node.position.borrow_mut() = position;
+
+                let size = ui.window_size();
+                self.node_rects.borrow_mut().insert(
+                    node.id(),
+                    (Vec2::new(x, y), Vec2::new(x + size[0], y + size[1])),
+                );
+
+                if ui.is_window_hovered() {
+                    self.hovered.set(Some(node.id()));
+                }
+
+                if self.node_menu.get() == Some(node.id()) {
+                    self.node_menu.set(None);
+                    *self.rename.borrow_mut() = node.name.clone();
+                    ui.open_popup("node menu");
+                }
+                ui.popup("node menu", || self.draw_node_menu(ui, node));
+
+                if (ui.io().key_ctrl || ui.io().key_shift)
+                    && ui.is_window_hovered()
+                    && ui.is_mouse_clicked(::imgui::MouseButton::Left)
+                {
+                    let mut selected = self.selected.borrow_mut();
+                    if !selected.remove(&node.id()) {
+                        selected.insert(node.id());
+                    }
+                }
 
                 let mut input_socket_iterator = node.inputs.iter();
                 let mut output_socket_iterator = node.outputs.iter();
@@ -339,20 +3052,46 @@ impl App {
                     }
 
                     let dl = ui.get_window_draw_list();
-                    let button_size = [PIN_RADIUS * 2.0, PIN_RADIUS * 2.0];
+                    let radius = PIN_RADIUS * camera.zoom;
+                    let button_size = [radius * 2.0, radius * 2.0];
 
                     match &node.kind {
-                        NodeKind::Input(enabled) => {
-                            let b = enabled.get() as u8 as f32;
-                            if ui.color_button("    ", [b, b, b, 1.0]) {
-                                enabled.set(!enabled.get());
-                                self.eval.set(true);
+                        NodeKind::Input(value) if node.width > 1 => {
+                            let mut bits = value.get().bits;
+                            ui.set_next_item_width(80.0);
+                            if ui
+                                .input_scalar("##value", &mut bits)
+                                .display_format("%llX")
+                                .build()
+                            {
+                                let old = value.get();
+                                value.set(Value::new(bits & mask(node.width)));
+                                self.record_value(node, old, value.get());
+                            }
+                        }
+
+                        NodeKind::Display(value) if node.width > 1 => {
+                            let value = value.get();
+                            let color = match value.is_known(node.width) {
+                                true => [1.0, 1.0, 1.0, 1.0],
+                                false => X_COLOR,
+                            };
+
+                            ui.text_colored(color, value.to_hex(node.width));
+                        }
+
+                        NodeKind::Input(value) => {
+                            let color = logic_color(value.get().bit(0));
+                            if ui.color_button("    ", color) {
+                                let old = value.get();
+                                value.set((!old).mask(1));
+                                self.record_value(node, old, value.get());
                             }
                         }
 
-                        NodeKind::Display(enabled) => {
-                            let b = enabled.get() as u8 as f32;
-                            if ui.color_button("    ", [b, b, b, 1.0]) {
+                        NodeKind::Display(value) => {
+                            let color = logic_color(value.get().bit(0));
+                            if ui.color_button("    ", color) {
                                 self.eval.set(true);
                             }
                         }
@@ -368,21 +3107,21 @@ impl App {
                         pin_center[0] += ui.item_rect_size()[0] / 2.0;
                         pin_center[1] += ui.item_rect_size()[1] / 2.0;
 
-                        dl.add_circle(pin_center, PIN_RADIUS, (1.0, 1.0, 0.0))
+                        dl.add_circle(pin_center, radius, self.pin_color(node, &i_borrow))
                             .build();
 
                         ui.same_line();
-                        ui.text(&i_borrow.name);
+                        ui.text(pin_label(&i_borrow));
 
                         drop(i_borrow);
                         i.borrow_mut().absolute_position =
-                            Some(Vector2::new(pin_center[0], pin_center[1]));
+                            Some(Vec2::new(pin_center[0], pin_center[1]));
                     }
 
                     if let Some(o) = output {
                         let o_borrow = o.borrow();
                         ui.same_line();
-                        ui.text(format!("     {}", o_borrow.name));
+                        ui.text(format!("     {}", pin_label(&o_borrow)));
                         ui.same_line();
 
                         ui.invisible_button(&o_borrow.name, button_size);
@@ -391,28 +3130,250 @@ impl App {
                         pin_center[0] += ui.item_rect_size()[0] / 2.0;
                         pin_center[1] += ui.item_rect_size()[1] / 2.0;
 
-                        dl.add_circle(pin_center, PIN_RADIUS, (1.0, 1.0, 0.0))
+                        dl.add_circle(pin_center, radius, self.pin_color(node, &o_borrow))
                             .build();
 
                         drop(o_borrow);
                         o.borrow_mut().absolute_position =
-                            Some(Vector2::new(pin_center[0], pin_center[1]));
+                            Some(Vec2::new(pin_center[0], pin_center[1]));
+                    }
+                }
+
+                if node.kind.has_width() {
+                    let mut width = node.width as i32;
+                    ui.set_next_item_width(80.0);
+                    if ui.input_int("bits", &mut width).build() {
+                        self.resize
+                            .set(Some((node.id(), width.clamp(1, MAX_WIDTH as i32) as u8)));
+                    }
+                }
+
+                if let NodeKind::Clock { period, duty } = &node.kind {
+                    let old = (period.get(), duty.get());
+
+                    let mut ticks = period.get() as i32;
+                    ui.set_next_item_width(80.0);
+                    if ui.input_int("period", &mut ticks).build() {
+                        period.set(ticks.max(1) as u32);
+                    }
+
+                    let mut high = duty.get();
+                    ui.set_next_item_width(80.0);
+                    if ui.slider("duty", 0.0, 1.0, &mut high) {
+                        duty.set(high);
+                    }
+
+                    let new = (period.get(), duty.get());
+                    if new != old {
+                        self.history.borrow_mut().push(
+                            format!("Change {}", node.name),
+                            Command::SetClock {
+                                node: node.id(),
+                                from: old,
+                                to: new,
+                            },
+                        );
+                        self.eval.set(true);
                     }
                 }
+
+                if self.evaluator.borrow().is_unstable(node.id()) {
+                    ui.text_colored([1.0, 0.3, 0.3, 1.0], "unstable");
+                }
             });
     }
 
+    fn draw_node_menu(&self, ui: &::imgui::Ui, node: &Node) {
+        let mut name = self.rename.borrow_mut();
+        ui.set_next_item_width(120.0);
+        if ui
+            .input_text("name", &mut name)
+            .enter_returns_true(true)
+            .build()
+        {
+            *self.node_action.borrow_mut() = Some(NodeAction::Rename(node.id(), name.clone()));
+            ui.close_current_popup();
+        }
+
+        ui.menu("Change type", || {
+            let chips = self.chips.borrow();
+            let chips = chips
+                .iter()
+                .map(|chip| NodeKind::Chip(Box::new(chip.clone())));
+
+            for kind in NodeKind::list().into_iter().chain(chips) {
+                if kind.to_string() == node.kind.to_string() {
+                    continue;
+                }
+
+                if ui.menu_item(kind.to_string()) {
+                    *self.node_action.borrow_mut() = Some(NodeAction::ChangeKind(node.id(), kind));
+                }
+            }
+        });
+
+        ui.separator();
+
+        // act on the whole selection if the node is part of it
+        let selected = self.selected.borrow();
+        let targets = match selected.contains(&node.id()) {
+            true => selected.iter().copied().collect(),
+            false => vec![node.id()],
+        };
+
+        if ui.menu_item_config("Duplicate").shortcut("Ctrl+D").build() {
+            *self.node_action.borrow_mut() = Some(NodeAction::Duplicate(targets.clone()));
+        }
+
+        if ui.menu_item_config("Delete").shortcut("Del").build() {
+            *self.node_action.borrow_mut() = Some(NodeAction::Delete(targets));
+        }
+    }
+
+    fn record_value(&self, node: &Node, from: Value, to: Value) {
+        self.history.borrow_mut().push(
+            format!("Set {}", node.name),
+            Command::SetValue {
+                node: node.id(),
+                from,
+                to,
+            },
+        );
+        self.eval.set(true);
+    }
+
+    /// Runs the simulation ticks that elapsed during the last frame
+    pub fn advance(&mut self, dt: f32) {
+        self.time += dt;
+
+        for _ in 0..self.simulation.advance(dt) {
+            let tick = self.simulation.tick.get() + 1;
+            self.simulation.tick.set(tick);
+
+            let nodes = self.nodes.borrow();
+            self.evaluator
+                .borrow_mut()
+                .evaluate(&nodes, nodes.dependency_graph(), tick);
+            self.start_pulses();
+        }
+    }
+
     pub fn update(&self) {
         if self.eval.get() {
-            println!("rebuilding");
-            dbg!(&self.dependency_graph);
-            self.nodes.borrow().iter().for_each(|i| {
-                i.eval(self, &self.dependency_graph);
-            });
+            let nodes = self.nodes.borrow();
+            self.evaluator.borrow_mut().evaluate(
+                &nodes,
+                nodes.dependency_graph(),
+                self.simulation.tick.get(),
+            );
+            self.start_pulses();
 
             self.eval.set(false);
         }
     }
+
+    /// Colour of the value on a pin, inputs show what their wires resolve to
+    fn pin_color(&self, node: &Node, socket: &Socket) -> [f32; 4] {
+        let socket_ref = SocketRef {
+            node_id: node.id(),
+            socket_id: socket.id,
+        };
+
+        let evaluator = self.evaluator.borrow();
+        let value = match socket.kind {
+            SocketKind::Input => {
+                Some(evaluator.input(socket_ref, self.nodes.borrow().dependency_graph()))
+            }
+            SocketKind::Output => evaluator.get(socket_ref),
+        };
+
+        value_color(value, socket.width)
+    }
+
+    /// Sends a pulse down the wires of every output that changed during the last evaluation,
+    /// outputs changed by another change wait for its pulse to arrive
+    fn start_pulses(&self) {
+        let evaluator = self.evaluator.borrow();
+        let mut last_values = self.last_values.borrow_mut();
+        let last = std::mem::replace(&mut *last_values, evaluator.values.clone());
+
+        if !self.animate.get() {
+            return;
+        }
+
+        // outputs that weren't there before have nothing to change from
+        let changed = evaluator
+            .values
+            .iter()
+            .filter_map(|(socket, value)| match last.get(socket) {
+                Some(from) if from != value => Some((*socket, *from)),
+                _ => None,
+            })
+            .collect::<HashMap<_, _>>();
+
+        if changed.is_empty() {
+            return;
+        }
+
+        let nodes = self.nodes.borrow();
+        let changed_nodes = changed
+            .keys()
+            .map(|socket| socket.node_id)
+            .collect::<HashSet<_>>();
+        let mut depths = HashMap::new();
+
+        let mut pulses = self.pulses.borrow_mut();
+        for (socket, from) in changed {
+            let depth = pulse_depth(
+                socket.node_id,
+                &nodes,
+                nodes.dependency_graph(),
+                &changed_nodes,
+                &mut depths,
+            );
+
+            pulses.insert(
+                socket,
+                Pulse {
+                    start: self.time + depth.min(MAX_PULSE_DEPTH) as f32 * PULSE_DURATION,
+                    from,
+                },
+            );
+        }
+    }
+}
+
+/// Straight pieces a wire is split into for hit-testing
+const BEZIER_SEGMENTS: usize = 32;
+
+/// Shape of a wire on screen
+enum Wire {
+    Curve(Bezier),
+    /// Orthogonal route through its corners
+    Lines(Polyline),
+}
+
+impl Wire {
+    fn draw(&self, thickness: f32, color: Color, d: &mut RaylibDrawHandle) {
+        match self {
+            Wire::Curve(bezier) => bezier.draw(thickness, color, d),
+            Wire::Lines(lines) => lines.draw_range(0.0, 1.0, thickness, color, d),
+        }
+    }
+
+    fn polyline(&self) -> Polyline {
+        match self {
+            Wire::Curve(bezier) => bezier.polyline(),
+            Wire::Lines(lines) => Polyline(lines.0.clone()),
+        }
+    }
+
+    fn distance(&self, point: Vec2) -> f32 {
+        match self {
+            Wire::Curve(bezier) => bezier.polyline().distance(point),
+            Wire::Lines(lines) => lines.distance(point),
+        }
+    }
 }
 
 struct Bezier {
@@ -427,4 +3388,174 @@ impl Bezier {
 
         d.draw_spline_bezier_cubic(&[self.p0, p1, p2, self.p3], thickness, color);
     }
+
+    fn point(&self, t: f32) -> Vec2 {
+        let (p0, p3) = (
+            Vec2::new(self.p0.x, self.p0.y),
+            Vec2::new(self.p3.x, self.p3.y),
+        );
+        let (p1, p2) = (Vec2::new(p3.x, p0.y), Vec2::new(p0.x, p3.y));
+        let u = 1.0 - t;
+
+        p0 * u * u * u + p1 * 3.0 * u * u * t + p2 * 3.0 * u * t * t + p3 * t * t * t
+    }
+
+    /// The curve split into straight pieces
+    fn polyline(&self) -> Polyline {
+        let points = (0..=BEZIER_SEGMENTS).map(|i| self.point(i as f32 / BEZIER_SEGMENTS as f32));
+        Polyline(points.collect())
+    }
+}
+
+/// Straight pieces through a list of points
+struct Polyline(Vec<Vec2>);
+
+impl Polyline {
+    fn length(&self) -> f32 {
+        self.0
+            .windows(2)
+            .map(|piece| piece[0].distance(piece[1]))
+            .sum()
+    }
+
+    /// Point `t` of the way along
+    fn point(&self, t: f32) -> Vec2 {
+        let mut left = t * self.length();
+        for piece in self.0.windows(2) {
+            let length = piece[0].distance(piece[1]);
+            if left <= length && length > 0.0 {
+                return piece[0].lerp(piece[1], left / length);
+            }
+            left -= length;
+        }
+
+        self.0.last().copied().unwrap_or_default()
+    }
+
+    /// Draws the part between `from` and `to` of the way along
+    fn draw_range(
+        &self,
+        from: f32,
+        to: f32,
+        thickness: f32,
+        color: Color,
+        d: &mut RaylibDrawHandle,
+    ) {
+        let length = self.length();
+        let (from, to) = (from * length, to * length);
+
+        let mut points = vec![self.point(from / length.max(f32::EPSILON))];
+        let mut travelled = 0.0;
+        for piece in self.0.windows(2) {
+            travelled += piece[0].distance(piece[1]);
+            if travelled > from && travelled < to {
+                points.push(piece[1]);
+            }
+        }
+        points.push(self.point(to / length.max(f32::EPSILON)));
+
+        for piece in points.windows(2) {
+            d.draw_line_ex(vector2(piece[0]), vector2(piece[1]), thickness, color);
+        }
+        // fills the gaps at the corners
+        for &corner in &points[1..points.len() - 1] {
+            d.draw_circle_v(vector2(corner), thickness / 2.0, color);
+        }
+    }
+
+    /// Distance from `point` to the closest piece
+    fn distance(&self, point: Vec2) -> f32 {
+        self.0
+            .windows(2)
+            .map(|segment| {
+                let (a, b) = (segment[0], segment[1]);
+                let t = ((point - a).dot(b - a) / (b - a).length_squared().max(f32::EPSILON))
+                    .clamp(0.0, 1.0);
+                point.distance(a + (b - a) * t)
+            })
+            .fold(f32::INFINITY, f32::min)
+    }
+}
+
+/// Copies of `nodes` with fresh node and socket ids, moved by `offset`, and `edges` between
+/// them pointed at the copies along with their routes
+fn duplicate(
+    nodes: &[Node],
+    edges: &[Edge],
+    routes: &Routes,
+    offset: Vec2,
+    ids: &mut IdAllocator,
+) -> (Vec<Node>, Vec<Edge>, Routes) {
+    let mut file = CircuitFile::new(nodes, edges.iter().copied());
+    file.set_routes(routes);
+
+    let Contents {
+        nodes: copies,
+        edges,
+        mut routes,
+        ..
+    } = file.build(ids);
+    for copy in &copies {
+        *copy.position.borrow_mut() += offset;
+    }
+    move_waypoints(&mut routes, offset);
+
+    (copies, edges, routes)
+}
+
+fn move_waypoints(routes: &mut Routes, offset: Vec2) {
+    for point in routes.values_mut().flat_map(|route| &mut route.waypoints) {
+        *point += offset;
+    }
+}
+
+/// Number of changed nodes in the longest chain leading up to `node`, each of them has to pass
+/// its change on before `node` changes
+fn pulse_depth(
+    node: NodeId,
+    nodes: &Circuit,
+    dep_graph: &DependencyGraph,
+    changed: &HashSet<NodeId>,
+    depths: &mut HashMap<NodeId, usize>,
+) -> usize {
+    if let Some(&depth) = depths.get(&node) {
+        return depth;
+    }
+
+    // stops feedback loops from recursing forever
+    depths.insert(node, 0);
+
+    let depth = nodes
+        .get(node)
+        .into_iter()
+        .flat_map(|node| &node.inputs)
+        .flat_map(|input| {
+            let input = SocketRef {
+                node_id: node,
+                socket_id: input.borrow().id,
+            };
+            dep_graph.get(&input).into_iter().flatten()
+        })
+        .filter(|driver| changed.contains(&driver.node_id))
+        .map(|driver| pulse_depth(driver.node_id, nodes, dep_graph, changed, depths) + 1)
+        .max()
+        .unwrap_or(0);
+
+    depths.insert(node, depth);
+    depth
+}
+
+/// Ids of the nodes matching `filter`, from top to bottom
+fn pins_by_height(nodes: &[Node], filter: impl Fn(&Node) -> bool) -> Vec<NodeId> {
+    let mut pins = nodes.iter().filter(|node| filter(node)).collect::<Vec<_>>();
+    pins.sort_by(|a, b| a.position.borrow().y.total_cmp(&b.position.borrow().y));
+    pins.into_iter().map(|node| node.id()).collect()
+}
+
+/// Buses show their width next to the name
+fn pin_label(socket: &Socket) -> String {
+    match socket.width {
+        1 => socket.name.clone(),
+        width => format!("{}[{width}]", socket.name),
+    }
 }
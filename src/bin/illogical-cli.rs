@@ -0,0 +1,294 @@
+//! Runs a saved circuit without opening a window, for checking circuits in CI.
+
+use std::cell::Cell;
+use std::path::Path;
+use std::process::ExitCode;
+
+use illogical::equivalence::{self, Method};
+use illogical::eval::{DependencyGraph, Evaluator, build_dependency_graph};
+use illogical::file::{CircuitFile, Contents};
+use illogical::logic::Value;
+use illogical::wire::*;
+
+const USAGE: &str = "\
+usage: illogical-cli <circuit.json> [options]
+       illogical-cli <circuit.json> --equiv <other.json>
+
+options:
+  --set NAME=VALUE      drive an input, can be repeated
+  --expect NAME=VALUE   check a display after running, can be repeated
+  --ticks N             clock ticks to simulate after applying the inputs, 0 by default
+  --vectors FILE        run every line of FILE as a separate step, lines look like
+                        `a=1 b=0 -> sum=1 carry=0`, lines starting with `#` are
+                        comments
+  --equiv FILE          check that FILE computes the same displays from the same inputs,
+                        matched by name, and print a counterexample if it doesn't
+
+Nodes are referred to by name, or by `#id` if the name isn't unique. Values are decimal,
+0x hex or 0b binary, `x` and `z` for unknown and undriven.
+
+Exits with 1 if an expectation fails or the circuits aren't equivalent and 2 if the circuit can't be run.";
+
+/// Inputs to apply and displays to check in one step of the run
+#[derive(Debug, Default)]
+struct Step {
+    set: Vec<(String, Value)>,
+    expect: Vec<(String, Value)>,
+}
+
+#[derive(Debug)]
+struct Args {
+    path: String,
+    ticks: u64,
+    steps: Vec<Step>,
+    /// Circuit to compare against instead of simulating
+    equiv: Option<String>,
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(1),
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+/// Returns `false` if any expectation failed
+fn run() -> Result<bool, String> {
+    let Some(args) = parse_args()? else {
+        println!("{USAGE}");
+        return Ok(true);
+    };
+
+    let (nodes, dep_graph) = load(&args.path)?;
+
+    if let Some(other) = &args.equiv {
+        let (other_nodes, other_graph) = load(other)?;
+        let report = equivalence::check(&nodes, &dep_graph, &other_nodes, &other_graph)?;
+
+        let method = match report.method {
+            Method::Exhaustive => "every input combination was simulated",
+            Method::Sat => "proved with SAT",
+        };
+
+        return Ok(match report.counterexample {
+            None => {
+                println!("equivalent, {method}");
+                true
+            }
+            Some(counterexample) => {
+                println!("not equivalent: {counterexample}");
+                false
+            }
+        });
+    }
+
+    let mut evaluator = Evaluator::new();
+    let mut tick = 0;
+    let mut passed = true;
+
+    for (idx, step) in args.steps.iter().enumerate() {
+        for (name, value) in &step.set {
+            let (input, width) = pin(&nodes, name, true)?;
+            input.set(value.mask(width));
+        }
+
+        evaluator.evaluate(&nodes, &dep_graph, tick);
+        for _ in 0..args.ticks {
+            tick += 1;
+            evaluator.evaluate(&nodes, &dep_graph, tick);
+        }
+
+        if !evaluator.unstable.is_empty() {
+            eprintln!(
+                "step {}: {} nodes did not settle",
+                idx + 1,
+                evaluator.unstable.len()
+            );
+        }
+
+        let outputs = nodes
+            .iter()
+            .filter_map(|node| match &node.kind {
+                NodeKind::Display(value) => Some(format!(
+                    "{}={}",
+                    node.label(&nodes),
+                    value.get().format(node.width)
+                )),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        println!("{}", outputs.join(" "));
+
+        for (name, expected) in &step.expect {
+            let (display, width) = pin(&nodes, name, false)?;
+            let actual = display.get().mask(width);
+
+            if actual != expected.mask(width) {
+                eprintln!(
+                    "step {}: expected {name}={} but got {}",
+                    idx + 1,
+                    expected.format(width),
+                    actual.format(width)
+                );
+                passed = false;
+            }
+        }
+    }
+
+    Ok(passed)
+}
+
+fn load(path: &str) -> Result<(Vec<Node>, DependencyGraph), String> {
+    let Contents { nodes, edges, .. } = CircuitFile::load(Path::new(path))
+        .and_then(CircuitFile::into_circuit)
+        .map_err(|err| format!("failed to open {path}: {err}"))?;
+
+    Ok((nodes, build_dependency_graph(edges)))
+}
+
+/// Returns `None` if only the usage was asked for
+fn parse_args() -> Result<Option<Args>, String> {
+    let mut args = std::env::args().skip(1);
+    let mut path = None;
+    let mut ticks = 0;
+    let mut step = Step::default();
+    let mut steps = vec![];
+    let mut vectors = false;
+    let mut equiv = None;
+
+    while let Some(arg) = args.next() {
+        let mut value = |option: &str| {
+            args.next()
+                .ok_or_else(|| format!("{option} needs a value\n\n{USAGE}"))
+        };
+
+        match arg.as_str() {
+            "--set" => step.set.push(parse_assignment(&value("--set")?)?),
+            "--expect" => step.expect.push(parse_assignment(&value("--expect")?)?),
+            "--ticks" => {
+                let value = value("--ticks")?;
+                ticks = value
+                    .parse()
+                    .map_err(|_| format!("invalid tick count {value}"))?;
+            }
+            "--vectors" => {
+                let file = value("--vectors")?;
+                let text = std::fs::read_to_string(&file)
+                    .map_err(|err| format!("failed to read {file}: {err}"))?;
+
+                steps.extend(parse_vectors(&text).map_err(|err| format!("{file}:{err}"))?);
+                vectors = true;
+            }
+            "--equiv" => equiv = Some(value("--equiv")?),
+            "-h" | "--help" => return Ok(None),
+
+            _ if arg.starts_with('-') => return Err(format!("unknown option {arg}\n\n{USAGE}")),
+            _ if path.is_none() => path = Some(arg),
+            _ => return Err(format!("unexpected argument {arg}\n\n{USAGE}")),
+        }
+    }
+
+    // values from the command line apply before the vectors from a file
+    if !vectors || !step.set.is_empty() || !step.expect.is_empty() {
+        steps.insert(0, step);
+    }
+
+    Ok(Some(Args {
+        path: path.ok_or_else(|| USAGE.to_string())?,
+        ticks,
+        steps,
+        equiv,
+    }))
+}
+
+fn parse_vectors(text: &str) -> Result<Vec<Step>, String> {
+    let mut steps = vec![];
+
+    for (idx, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (set, expect) = line.split_once("->").unwrap_or((line, ""));
+        let assignments = |part: &str| {
+            part.split_whitespace()
+                .map(parse_assignment)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|err| format!("{}: {err}", idx + 1))
+        };
+
+        steps.push(Step {
+            set: assignments(set)?,
+            expect: assignments(expect)?,
+        });
+    }
+
+    Ok(steps)
+}
+
+fn parse_assignment(text: &str) -> Result<(String, Value), String> {
+    let (name, value) = text
+        .split_once('=')
+        .ok_or_else(|| format!("expected NAME=VALUE, got {text}"))?;
+
+    let value = parse_value(value).ok_or_else(|| format!("invalid value {value} for {name}"))?;
+    Ok((name.to_string(), value))
+}
+
+fn parse_value(text: &str) -> Option<Value> {
+    let text = text.to_ascii_lowercase();
+
+    let bits = match text.as_str() {
+        "x" => return Some(Value::X),
+        "z" => return Some(Value::Z),
+        _ if text.starts_with("0x") => u64::from_str_radix(&text[2..], 16),
+        _ if text.starts_with("0b") => u64::from_str_radix(&text[2..], 2),
+        _ => text.parse(),
+    };
+
+    bits.ok().map(Value::new)
+}
+
+/// Finds the value cell and width of an Input (or Display) node by name or `#id`
+fn pin<'a>(nodes: &'a [Node], name: &str, input: bool) -> Result<(&'a Cell<Value>, u8), String> {
+    let id = name
+        .strip_prefix('#')
+        .and_then(|id| id.parse().ok().map(NodeId));
+
+    let matches = nodes
+        .iter()
+        .filter_map(|node| {
+            let value = match (&node.kind, input) {
+                (NodeKind::Input(value), true) | (NodeKind::Display(value), false) => value,
+                _ => return None,
+            };
+
+            let found = match id {
+                Some(id) => node.id() == id,
+                None => node.name == name,
+            };
+
+            found.then_some((value, node.width))
+        })
+        .collect::<Vec<_>>();
+
+    let kind = match input {
+        true => "input",
+        false => "display",
+    };
+
+    match matches.as_slice() {
+        [pin] => Ok(*pin),
+        [] => Err(format!("no {kind} named {name}")),
+        _ => Err(format!(
+            "{} {kind}s are named {name}, use #id instead",
+            matches.len()
+        )),
+    }
+}
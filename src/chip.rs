@@ -0,0 +1,318 @@
+use std::cell::RefCell;
+use std::hash::{BuildHasher, RandomState};
+use std::io;
+
+use serde::{Deserialize, Serialize};
+
+use crate::circuit::Circuit;
+use crate::eval::{Evaluator, Schedule};
+use crate::file::{CircuitFile, Contents};
+use crate::logic::Value;
+use crate::wire::*;
+
+/// Identifies a chip through edits and copies, instances are matched up with the palette by it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ChipId(pub u64);
+
+impl ChipId {
+    /// Random, so chips made in different files don't collide when pasted together
+    pub fn new() -> Self {
+        Self(RandomState::new().hash_one(0))
+    }
+
+    /// Files saved before chips had ids can only tell them apart by name. FNV-1a, the id has to
+    /// come out the same with every build.
+    fn from_name(name: &str) -> Self {
+        let hash = name.bytes().fold(0xcbf2_9ce4_8422_2325, |hash: u64, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+        });
+        Self(hash)
+    }
+}
+
+impl Default for ChipId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A circuit packaged as a single node, its pins are the exposed Input and Display nodes.
+///
+/// Every instance owns a copy of the circuit so flip-flops inside keep their own state.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(into = "ChipFile", try_from = "ChipFile")]
+pub struct Chip {
+    pub id: ChipId,
+    pub name: String,
+    pub nodes: Circuit,
+    /// Input nodes driven by the chip's input pins, in pin order
+    pub inputs: Vec<NodeId>,
+    /// Display nodes feeding the chip's output pins, in pin order
+    pub outputs: Vec<NodeId>,
+
+    schedule: Schedule,
+    evaluator: RefCell<Evaluator>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ChipFile {
+    #[serde(default)]
+    id: Option<ChipId>,
+    name: String,
+    circuit: CircuitFile,
+    inputs: Vec<NodeId>,
+    outputs: Vec<NodeId>,
+}
+
+impl From<Chip> for ChipFile {
+    fn from(chip: Chip) -> Self {
+        Self {
+            circuit: CircuitFile::new(&chip.nodes, chip.nodes.edges().iter().copied()),
+            id: Some(chip.id),
+            name: chip.name,
+            inputs: chip.inputs,
+            outputs: chip.outputs,
+        }
+    }
+}
+
+impl TryFrom<ChipFile> for Chip {
+    type Error = io::Error;
+
+    fn try_from(file: ChipFile) -> io::Result<Self> {
+        let Contents { nodes, edges, .. } = file.circuit.into_circuit()?;
+        let id = file.id.unwrap_or_else(|| ChipId::from_name(&file.name));
+        Ok(Chip {
+            id,
+            ..Chip::new(file.name, nodes, edges, file.inputs, file.outputs)
+        })
+    }
+}
+
+impl Chip {
+    /// A chip of its own with a new id
+    pub fn new(
+        name: String,
+        nodes: Vec<Node>,
+        edges: Vec<Edge>,
+        inputs: Vec<NodeId>,
+        outputs: Vec<NodeId>,
+    ) -> Self {
+        let mut nodes = Circuit::from(nodes);
+        nodes.rewire(edges);
+        let schedule = Schedule::new(&nodes, nodes.dependency_graph());
+
+        Self {
+            id: ChipId::new(),
+            name,
+            nodes,
+            inputs,
+            outputs,
+            schedule,
+            evaluator: Evaluator::new().into(),
+        }
+    }
+
+    /// Replaces the instances of `chip` inside this one with copies of it, at any depth.
+    /// Returns `false` if there were none.
+    pub fn update_instances(&mut self, chip: &Chip) -> bool {
+        let mut ids = IdAllocator::after(&self.nodes);
+        if update_instances(&mut self.nodes, &mut ids, chip).is_empty() {
+            return false;
+        }
+
+        // wires to pins that are gone or changed width
+        let nodes = std::mem::take(&mut self.nodes);
+        let width = |socket| nodes.socket(socket).map(|socket| socket.borrow().width);
+        let edges = nodes
+            .edges()
+            .iter()
+            .copied()
+            .filter(|edge| width(edge.from).is_some() && width(edge.from) == width(edge.to))
+            .collect();
+
+        *self = Chip {
+            id: self.id,
+            ..Chip::new(
+                std::mem::take(&mut self.name),
+                nodes.into_vec(),
+                edges,
+                std::mem::take(&mut self.inputs),
+                std::mem::take(&mut self.outputs),
+            )
+        };
+        true
+    }
+
+    fn node(&self, id: NodeId) -> Option<&Node> {
+        self.nodes.get(id)
+    }
+
+    /// Pins are named after their nodes and as wide as them
+    fn pins(&self, ids: &[NodeId]) -> Vec<(String, u8)> {
+        ids.iter()
+            .map(|&id| {
+                self.node(id)
+                    .map(|node| (node.name.clone(), node.width))
+                    .unwrap_or_else(|| (String::new(), 1))
+            })
+            .collect()
+    }
+
+    pub fn input_pins(&self) -> Vec<(String, u8)> {
+        self.pins(&self.inputs)
+    }
+
+    pub fn output_pins(&self) -> Vec<(String, u8)> {
+        self.pins(&self.outputs)
+    }
+
+    /// Drives the exposed Input nodes, X and Z pass through into the chip
+    fn set_inputs(&self, inputs: &[Value]) {
+        for (&id, value) in self.inputs.iter().zip(inputs) {
+            if let Some(NodeKind::Input(cell)) = self.node(id).map(|node| &node.kind) {
+                cell.set(*value);
+            }
+        }
+    }
+
+    pub fn eval(&self, inputs: &[Value], tick: u64) -> Vec<Value> {
+        self.set_inputs(inputs);
+
+        self.evaluator.borrow_mut().settle(
+            &self.nodes,
+            self.nodes.dependency_graph(),
+            &self.schedule,
+            tick,
+        );
+
+        // displays hold whatever reached them during the settle
+        self.outputs
+            .iter()
+            .map(|&id| match self.node(id).map(|node| &node.kind) {
+                Some(NodeKind::Display(value)) => value.get(),
+                _ => Value::X,
+            })
+            .collect()
+    }
+
+    /// Clocks the flip-flops inside, returns `true` if any of them changed
+    pub fn update(&self, inputs: &[Value], tick: u64) -> bool {
+        self.set_inputs(inputs);
+
+        let mut evaluator = self.evaluator.borrow_mut();
+        evaluator.settle(
+            &self.nodes,
+            self.nodes.dependency_graph(),
+            &self.schedule,
+            tick,
+        );
+
+        !evaluator
+            .clock(
+                &self.nodes,
+                self.nodes.dependency_graph(),
+                &self.schedule,
+                tick,
+            )
+            .is_empty()
+    }
+}
+
+/// Replaces the instances of `chip` in `nodes` with copies of it, including the ones nested in
+/// other chips. Returns the ids of the nodes that changed, wires to pins that are gone are left
+/// for the caller to drop.
+pub fn update_instances(nodes: &mut Circuit, ids: &mut IdAllocator, chip: &Chip) -> Vec<NodeId> {
+    nodes
+        .iter_mut()
+        .filter_map(|node| update_instance(node, ids, chip).then_some(node.id()))
+        .collect()
+}
+
+/// Same as `update_instances` for a single node, returns `false` if it didn't change
+pub fn update_instance(node: &mut Node, ids: &mut IdAllocator, chip: &Chip) -> bool {
+    let NodeKind::Chip(instance) = &mut node.kind else {
+        return false;
+    };
+
+    if instance.id == chip.id {
+        node.set_kind(NodeKind::Chip(Box::new(chip.clone())), ids);
+        true
+    } else {
+        instance.update_instances(chip)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec2;
+
+    use super::*;
+
+    fn chip(name: &str, kinds: Vec<NodeKind>) -> Chip {
+        let mut ids = IdAllocator::new();
+        let nodes = kinds
+            .into_iter()
+            .map(|kind| kind.build(Vec2::ZERO, &mut ids))
+            .collect::<Vec<_>>();
+        let inputs = nodes.iter().map(|node| node.id()).collect();
+        Chip::new(name.to_string(), nodes, vec![], inputs, vec![])
+    }
+
+    fn instances(nodes: &Circuit) -> Vec<(ChipId, usize)> {
+        nodes
+            .iter()
+            .filter_map(|node| match &node.kind {
+                NodeKind::Chip(chip) => Some((chip.id, chip.nodes.len())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn instances_are_matched_by_id_not_name() {
+        let input = NodeKind::Input(Default::default());
+        let (a, b) = (
+            chip("half", vec![input.clone()]),
+            chip("half", vec![input.clone()]),
+        );
+
+        let mut ids = IdAllocator::new();
+        let mut nodes = Circuit::from(vec![
+            NodeKind::Chip(Box::new(a.clone())).build(Vec2::ZERO, &mut ids),
+            NodeKind::Chip(Box::new(b.clone())).build(Vec2::ZERO, &mut ids),
+        ]);
+
+        let mut edited = chip("half", vec![input.clone(), input]);
+        edited.id = a.id;
+
+        let changed = update_instances(&mut nodes, &mut ids, &edited);
+        assert_eq!(changed, vec![nodes[0].id()]);
+        assert_eq!(instances(&nodes), vec![(a.id, 2), (b.id, 1)]);
+    }
+
+    #[test]
+    fn nested_instances_are_updated() {
+        let input = NodeKind::Input(Default::default());
+        let inner = chip("inner", vec![input.clone()]);
+        let mut outer = chip("outer", vec![NodeKind::Chip(Box::new(inner.clone()))]);
+
+        let mut edited = chip("inner", vec![input.clone(), input]);
+        edited.id = inner.id;
+
+        assert!(outer.update_instances(&edited));
+        assert_eq!(instances(&outer.nodes), vec![(inner.id, 2)]);
+        assert!(!outer.update_instances(&chip("other", vec![])));
+    }
+
+    #[test]
+    fn chips_saved_without_an_id_are_told_apart_by_name() {
+        let text = r#"{"name": "adder", "circuit": {"version": 1, "nodes": [], "edges": []},
+            "inputs": [], "outputs": []}"#;
+        let load = || serde_json::from_str::<Chip>(text).unwrap().id;
+
+        assert_eq!(load(), ChipId(0x235d_ecfd_fd54_3243));
+        assert_ne!(load(), chip("adder", vec![]).id);
+    }
+}
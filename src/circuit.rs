@@ -0,0 +1,378 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::ops::Deref;
+
+use crate::eval::{DependencyGraph, Fanout};
+use crate::wire::*;
+
+/// Nodes of a circuit in the order they were added, indexed by id, with the wires between
+/// their sockets.
+///
+/// Dereferences to a slice so it can be passed wherever a `&[Node]` is expected, lookups by id
+/// go through the index instead of scanning. Wires are kept in the order they were made and
+/// both ways, the drivers of every input and the inputs every output drives, and are dropped
+/// along with the nodes they touch.
+#[derive(Debug, Clone, Default)]
+pub struct Circuit {
+    nodes: Vec<Node>,
+    // position of every node in `nodes`
+    index: HashMap<NodeId, usize>,
+    edges: Vec<Edge>,
+    drivers: DependencyGraph,
+    fanout: Fanout,
+}
+
+impl Circuit {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, id: NodeId) -> Option<&Node> {
+        self.index.get(&id).map(|&idx| &self.nodes[idx])
+    }
+
+    pub fn get_mut(&mut self, id: NodeId) -> Option<&mut Node> {
+        self.index.get(&id).map(|&idx| &mut self.nodes[idx])
+    }
+
+    pub fn socket(&self, socket: SocketRef) -> Option<&RefCell<Socket>> {
+        self.get(socket.node_id)?.socket(socket.socket_id)
+    }
+
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, Node> {
+        self.nodes.iter_mut()
+    }
+
+    /// Every wire, oldest first
+    pub fn edges(&self) -> &[Edge] {
+        &self.edges
+    }
+
+    /// Output sockets wired to `input`
+    pub fn drivers(&self, input: SocketRef) -> &[SocketRef] {
+        self.drivers.get(&input).map_or(&[], Vec::as_slice)
+    }
+
+    /// Input sockets wired to `output`
+    pub fn fanout(&self, output: SocketRef) -> &[SocketRef] {
+        self.fanout.get(&output).map_or(&[], Vec::as_slice)
+    }
+
+    /// Drivers of every connected input
+    pub fn dependency_graph(&self) -> &DependencyGraph {
+        &self.drivers
+    }
+
+    pub fn connect(&mut self, edge: Edge) {
+        self.connect_at(self.edges.len(), edge);
+    }
+
+    /// Adds a wire at `idx` in the order of `edges`, or at the end if there are fewer
+    pub fn connect_at(&mut self, idx: usize, edge @ Edge { from, to }: Edge) {
+        self.edges.insert(idx.min(self.edges.len()), edge);
+        self.drivers.entry(to).or_default().push(from);
+        self.fanout.entry(from).or_default().push(to);
+    }
+
+    pub fn disconnect(&mut self, edge: Edge) {
+        self.edges.retain(|&other| other != edge);
+        self.unlink(edge);
+    }
+
+    /// Drops the wires `keep` turns down, returns them with the index each had
+    pub fn retain_edges(&mut self, mut keep: impl FnMut(Edge) -> bool) -> Vec<(usize, Edge)> {
+        let mut removed = vec![];
+        let mut idx = 0;
+        self.edges.retain(|&edge| {
+            let kept = keep(edge);
+            if !kept {
+                removed.push((idx, edge));
+            }
+            idx += 1;
+            kept
+        });
+
+        for &(_, edge) in &removed {
+            self.unlink(edge);
+        }
+        removed
+    }
+
+    /// Replaces every wire with `edges`
+    pub fn rewire(&mut self, edges: impl IntoIterator<Item = Edge>) {
+        self.edges.clear();
+        self.drivers.clear();
+        self.fanout.clear();
+        for edge in edges {
+            self.connect(edge);
+        }
+    }
+
+    // drops a wire from the maps both ways
+    fn unlink(&mut self, Edge { from, to }: Edge) {
+        let unlink = |map: &mut HashMap<SocketRef, Vec<SocketRef>>, key, value| {
+            if let Some(sockets) = map.get_mut(&key) {
+                sockets.retain(|&socket| socket != value);
+                if sockets.is_empty() {
+                    map.remove(&key);
+                }
+            }
+        };
+
+        unlink(&mut self.drivers, to, from);
+        unlink(&mut self.fanout, from, to);
+    }
+
+    pub fn push(&mut self, node: Node) {
+        debug_assert!(!self.index.contains_key(&node.id()), "duplicate node id");
+        self.index.insert(node.id(), self.nodes.len());
+        self.nodes.push(node);
+    }
+
+    pub fn insert(&mut self, idx: usize, node: Node) {
+        debug_assert!(!self.index.contains_key(&node.id()), "duplicate node id");
+        self.nodes.insert(idx, node);
+        self.reindex(idx);
+    }
+
+    pub fn remove(&mut self, idx: usize) -> Node {
+        let node = self.nodes.remove(idx);
+        self.index.remove(&node.id());
+        self.reindex(idx);
+        self.unwire(|id| id == node.id());
+        node
+    }
+
+    /// Puts `node` in place of the node with the same id, returns the one it replaced
+    pub fn replace(&mut self, node: Node) -> Option<Node> {
+        let &idx = self.index.get(&node.id())?;
+        Some(std::mem::replace(&mut self.nodes[idx], node))
+    }
+
+    /// Removes the nodes `pick` chooses along with their wires, returns them with the index each
+    /// had
+    pub fn extract_if(&mut self, mut pick: impl FnMut(&Node) -> bool) -> Vec<(usize, Node)> {
+        let mut removed = vec![];
+        let mut kept = vec![];
+        for (idx, node) in std::mem::take(&mut self.nodes).into_iter().enumerate() {
+            match pick(&node) {
+                true => removed.push((idx, node)),
+                false => kept.push(node),
+            }
+        }
+
+        self.nodes = kept;
+        self.index.clear();
+        self.reindex(0);
+
+        let gone = removed
+            .iter()
+            .map(|(_, node)| node.id())
+            .collect::<HashSet<_>>();
+        self.unwire(|id| gone.contains(&id));
+        removed
+    }
+
+    pub fn retain(&mut self, mut keep: impl FnMut(&Node) -> bool) {
+        self.extract_if(|node| !keep(node));
+    }
+
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.index.clear();
+        self.edges.clear();
+        self.drivers.clear();
+        self.fanout.clear();
+    }
+
+    pub fn into_vec(self) -> Vec<Node> {
+        self.nodes
+    }
+
+    // nodes from `start` on have moved
+    fn reindex(&mut self, start: usize) {
+        for (idx, node) in self.nodes.iter().enumerate().skip(start) {
+            self.index.insert(node.id(), idx);
+        }
+    }
+
+    // drops the wires touching the nodes that are gone
+    fn unwire(&mut self, gone: impl Fn(NodeId) -> bool) {
+        self.edges
+            .retain(|edge| !gone(edge.from.node_id) && !gone(edge.to.node_id));
+        for map in [&mut self.drivers, &mut self.fanout] {
+            map.retain(|key, sockets| {
+                sockets.retain(|socket| !gone(socket.node_id));
+                !gone(key.node_id) && !sockets.is_empty()
+            });
+        }
+    }
+}
+
+impl Deref for Circuit {
+    type Target = [Node];
+
+    fn deref(&self) -> &[Node] {
+        &self.nodes
+    }
+}
+
+impl From<Vec<Node>> for Circuit {
+    fn from(nodes: Vec<Node>) -> Self {
+        let mut circuit = Self {
+            nodes,
+            ..Self::default()
+        };
+        circuit.reindex(0);
+        debug_assert_eq!(
+            circuit.index.len(),
+            circuit.nodes.len(),
+            "duplicate node ids"
+        );
+        circuit
+    }
+}
+
+impl FromIterator<Node> for Circuit {
+    fn from_iter<I: IntoIterator<Item = Node>>(iter: I) -> Self {
+        Self::from(iter.into_iter().collect::<Vec<_>>())
+    }
+}
+
+impl Extend<Node> for Circuit {
+    fn extend<I: IntoIterator<Item = Node>>(&mut self, iter: I) {
+        for node in iter {
+            self.push(node);
+        }
+    }
+}
+
+impl IntoIterator for Circuit {
+    type Item = Node;
+    type IntoIter = std::vec::IntoIter<Node>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.nodes.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Circuit {
+    type Item = &'a Node;
+    type IntoIter = std::slice::Iter<'a, Node>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.nodes.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut Circuit {
+    type Item = &'a mut Node;
+    type IntoIter = std::slice::IterMut<'a, Node>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.nodes.iter_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec2;
+
+    use super::*;
+
+    fn pin(node: &Node, kind: SocketKind) -> SocketRef {
+        let sockets = match kind {
+            SocketKind::Input => &node.inputs,
+            SocketKind::Output => &node.outputs,
+        };
+        SocketRef {
+            node_id: node.id(),
+            socket_id: sockets[0].borrow().id,
+        }
+    }
+
+    // a chain of three inverters
+    fn chain() -> (Circuit, [SocketRef; 4]) {
+        let mut ids = IdAllocator::new();
+        let mut circuit = Circuit::new();
+        circuit.extend((0..3).map(|_| NodeKind::Not.build(Vec2::ZERO, &mut ids)));
+
+        let a = Edge {
+            from: pin(&circuit[0], SocketKind::Output),
+            to: pin(&circuit[1], SocketKind::Input),
+        };
+        let b = Edge {
+            from: pin(&circuit[1], SocketKind::Output),
+            to: pin(&circuit[2], SocketKind::Input),
+        };
+        circuit.rewire([a, b]);
+        (circuit, [a.from, a.to, b.from, b.to])
+    }
+
+    #[test]
+    fn wires_are_kept_both_ways() {
+        let (mut circuit, [out0, in1, out1, in2]) = chain();
+        assert_eq!(circuit.drivers(in1), [out0]);
+        assert_eq!(circuit.fanout(out0), [in1]);
+        assert_eq!(circuit.fanout(out1), [in2]);
+
+        let first = Edge {
+            from: out0,
+            to: in1,
+        };
+        circuit.disconnect(first);
+        assert!(circuit.drivers(in1).is_empty());
+        assert!(circuit.fanout(out0).is_empty());
+        assert_eq!(circuit.dependency_graph().len(), 1);
+
+        circuit.connect_at(0, first);
+        assert_eq!(circuit.edges()[0], first);
+        assert_eq!(circuit.drivers(in1), [out0]);
+    }
+
+    #[test]
+    fn wires_go_with_their_nodes() {
+        let (mut circuit, [out0, .., in2]) = chain();
+        circuit.remove(1);
+        assert!(circuit.fanout(out0).is_empty());
+        assert!(circuit.drivers(in2).is_empty());
+        assert!(circuit.dependency_graph().is_empty());
+        assert!(circuit.edges().is_empty());
+
+        let (mut circuit, [out0, in1, out1, in2]) = chain();
+        let last = in2.node_id;
+        circuit.retain(|node| node.id() != last);
+        assert_eq!(circuit.drivers(in1), [out0]);
+        assert!(circuit.fanout(out1).is_empty());
+        assert_eq!(circuit.get(last).map(|node| node.id()), None);
+    }
+
+    #[test]
+    fn removed_nodes_and_wires_go_back_in_place() {
+        let (mut circuit, _) = chain();
+        let (ids, edges) = (
+            circuit.iter().map(|node| node.id()).collect::<Vec<_>>(),
+            circuit.edges().to_vec(),
+        );
+
+        let middle = ids[1];
+        let wires =
+            circuit.retain_edges(|edge| edge.from.node_id != middle && edge.to.node_id != middle);
+        let nodes = circuit.extract_if(|node| node.id() == middle);
+        assert_eq!(nodes.len(), 1);
+        assert!(circuit.edges().is_empty());
+
+        for (idx, node) in nodes {
+            circuit.insert(idx, node);
+        }
+        for (idx, edge) in wires {
+            circuit.connect_at(idx, edge);
+        }
+        assert_eq!(
+            circuit.iter().map(|node| node.id()).collect::<Vec<_>>(),
+            ids
+        );
+        assert_eq!(circuit.edges(), edges);
+        assert_eq!(circuit.get(middle).map(|node| node.id()), Some(middle));
+    }
+}
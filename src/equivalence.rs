@@ -0,0 +1,581 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::eval::{DependencyGraph, Evaluator, Schedule};
+use crate::logic::{Logic, Value};
+use crate::sat::{Lit, Solver};
+use crate::wire::*;
+
+/// Circuits with up to this many input bits that the SAT solver can't take are compared by
+/// simulating every combination
+pub const MAX_EXHAUSTIVE_BITS: u32 = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    Exhaustive,
+    Sat,
+}
+
+/// Value of an Input or Display node
+#[derive(Debug, Clone)]
+pub struct Signal {
+    pub name: String,
+    pub width: u8,
+    pub value: Value,
+}
+
+/// Inputs on which two circuits disagree
+#[derive(Debug, Clone)]
+pub struct Counterexample {
+    pub inputs: Vec<Signal>,
+    /// Displays that differ, with their value in the first circuit and in the second
+    pub outputs: Vec<(Signal, Value)>,
+}
+
+impl std::fmt::Display for Counterexample {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let inputs = self
+            .inputs
+            .iter()
+            .map(|input| format!("{}={}", input.name, input.value.format(input.width)));
+        let outputs = self.outputs.iter().map(|(output, other)| {
+            format!(
+                "{}={} vs {}",
+                output.name,
+                output.value.format(output.width),
+                other.format(output.width)
+            )
+        });
+
+        write!(
+            f,
+            "{} -> {}",
+            inputs.collect::<Vec<_>>().join(" "),
+            outputs.collect::<Vec<_>>().join(", ")
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub method: Method,
+    /// `None` if the circuits are equivalent
+    pub counterexample: Option<Counterexample>,
+}
+
+/// Circuit under comparison
+struct Side<'a> {
+    nodes: &'a [Node],
+    dep_graph: &'a DependencyGraph,
+}
+
+/// Checks whether two circuits drive their Display nodes the same for every combination of their
+/// Input nodes, which are matched up by name.
+///
+/// Circuits made of gates alone are handed to the SAT solver, anything else is simulated
+/// exhaustively, which only small circuits can be.
+pub fn check(
+    a: &[Node],
+    a_graph: &DependencyGraph,
+    b: &[Node],
+    b_graph: &DependencyGraph,
+) -> Result<Report, String> {
+    let sides = [
+        Side {
+            nodes: a,
+            dep_graph: a_graph,
+        },
+        Side {
+            nodes: b,
+            dep_graph: b_graph,
+        },
+    ];
+
+    let is_input = |kind: &NodeKind| matches!(kind, NodeKind::Input(_));
+    let is_display = |kind: &NodeKind| matches!(kind, NodeKind::Display(_));
+
+    let inputs = pins(&sides, is_input, "input")?;
+    let outputs = pins(&sides, is_display, "display")?;
+    let bits = inputs.iter().map(|(_, width)| *width as u32).sum::<u32>();
+
+    let sat = solve(&sides, &inputs, &outputs);
+    if sat.is_ok() || bits > MAX_EXHAUSTIVE_BITS {
+        return sat;
+    }
+
+    let mut benches = sides.each_ref().map(|side| Bench::new(side, &outputs));
+    let counterexample = (0..1u64 << bits).find_map(|row| {
+        // the last input takes the lowest bits
+        let mut rest = row;
+        let mut assignment = inputs
+            .iter()
+            .rev()
+            .map(|(name, width)| {
+                let value = Value::new(rest & mask(*width));
+                rest = rest.checked_shr(*width as u32).unwrap_or(0);
+                (name.clone(), value)
+            })
+            .collect::<Vec<_>>();
+        assignment.reverse();
+
+        compare(&mut benches, &inputs, &outputs, &assignment)
+    });
+
+    Ok(Report {
+        method: Method::Exhaustive,
+        counterexample,
+    })
+}
+
+/// Looks for inputs telling the circuits apart with the SAT solver, fails on anything but gates
+/// and when simulating the inputs it found doesn't confirm them
+fn solve(
+    sides: &[Side; 2],
+    inputs: &[(String, u8)],
+    outputs: &[(String, u8)],
+) -> Result<Report, String> {
+    let is_display = |kind: &NodeKind| matches!(kind, NodeKind::Display(_));
+
+    let mut solver = Solver::new();
+    let mut variables = HashMap::new();
+    for (name, width) in inputs {
+        let bits = (0..*width).map(|_| solver.new_var()).collect::<Vec<_>>();
+        variables.insert(name.clone(), bits);
+    }
+
+    let mut encoded = vec![];
+    for side in sides {
+        let mut encoder = Encoder {
+            side,
+            solver: &mut solver,
+            variables: &variables,
+            encoded: HashMap::new(),
+            visiting: HashSet::new(),
+        };
+
+        let bits = outputs
+            .iter()
+            .map(|(name, _)| {
+                let display = side
+                    .nodes
+                    .iter()
+                    .find(|node| &node.name == name && is_display(&node.kind))
+                    .unwrap();
+                encoder.input(display, 0)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        encoded.push(bits);
+    }
+
+    // miter, satisfiable if any output bit differs
+    let differences = encoded[0]
+        .iter()
+        .flatten()
+        .zip(encoded[1].iter().flatten())
+        .map(|(&a, &b)| xor(&mut solver, a, b))
+        .collect::<Vec<_>>();
+    solver.add_clause(&differences);
+
+    let Some(model) = solver.solve() else {
+        return Ok(Report {
+            method: Method::Sat,
+            counterexample: None,
+        });
+    };
+
+    let assignment = inputs
+        .iter()
+        .map(|(name, _)| {
+            let bits = variables[name].iter().enumerate();
+            let value = bits
+                .filter(|(_, lit)| model[lit.var()])
+                .fold(0, |value, (bit, _)| value | 1 << bit);
+            (name.clone(), Value::new(value))
+        })
+        .collect::<Vec<_>>();
+
+    let mut benches = sides.each_ref().map(|side| Bench::new(side, outputs));
+    match compare(&mut benches, inputs, outputs, &assignment) {
+        Some(counterexample) => Ok(Report {
+            method: Method::Sat,
+            counterexample: Some(counterexample),
+        }),
+        // the encoding and the simulation disagree, neither answer can be trusted
+        None => Err(
+            "the inputs the solver found don't tell the circuits apart when simulated".to_string(),
+        ),
+    }
+}
+
+/// Names and widths of the pins of the first circuit, which have to match the second's
+fn pins(
+    sides: &[Side; 2],
+    is_pin: fn(&NodeKind) -> bool,
+    kind: &str,
+) -> Result<Vec<(String, u8)>, String> {
+    let [a, b] = sides.each_ref().map(|side| {
+        side.nodes
+            .iter()
+            .filter(|node| is_pin(&node.kind))
+            .map(|node| (node.name.clone(), node.width))
+            .collect::<Vec<_>>()
+    });
+
+    for (idx, pins) in [&a, &b].into_iter().enumerate() {
+        let mut names = HashSet::new();
+        if let Some((name, _)) = pins.iter().find(|(name, _)| !names.insert(name)) {
+            return Err(format!(
+                "circuit {} has several {kind}s named {name}",
+                idx + 1
+            ));
+        }
+    }
+
+    for (name, width) in &a {
+        match b.iter().find(|(other, _)| other == name) {
+            None => return Err(format!("the second circuit has no {kind} named {name}")),
+            Some((_, other)) if other != width => {
+                return Err(format!(
+                    "{kind} {name} is {width} bits wide in the first circuit and {other} in the second"
+                ));
+            }
+            Some(_) => {}
+        }
+    }
+
+    if let Some((name, _)) = b
+        .iter()
+        .find(|(name, _)| !a.iter().any(|(other, _)| other == name))
+    {
+        return Err(format!("the first circuit has no {kind} named {name}"));
+    }
+
+    Ok(a)
+}
+
+/// Copy of a circuit set up to be simulated on one input combination after another
+struct Bench<'a> {
+    side: &'a Side<'a>,
+    nodes: Vec<Node>,
+    schedule: Schedule,
+    evaluator: Evaluator,
+    /// Inputs, displays and nodes with state, put back as they were before every run
+    reset: Vec<usize>,
+    inputs: HashMap<String, usize>,
+    /// Display of every output
+    displays: Vec<usize>,
+}
+
+impl<'a> Bench<'a> {
+    fn new(side: &'a Side<'a>, outputs: &[(String, u8)]) -> Self {
+        let nodes = side.nodes.to_vec();
+        let index = |is_pin: fn(&NodeKind) -> bool| {
+            let pins = nodes
+                .iter()
+                .enumerate()
+                .filter(move |(_, node)| is_pin(&node.kind));
+            pins.map(|(idx, node)| (node.name.clone(), idx))
+        };
+
+        let displays =
+            index(|kind| matches!(kind, NodeKind::Display(_))).collect::<HashMap<_, _>>();
+        let reset = nodes.iter().enumerate().filter(|(_, node)| {
+            node.kind.has_state() || matches!(node.kind, NodeKind::Input(_) | NodeKind::Display(_))
+        });
+
+        Self {
+            side,
+            schedule: Schedule::new(&nodes, side.dep_graph),
+            evaluator: Evaluator::new(),
+            reset: reset.map(|(idx, _)| idx).collect(),
+            inputs: index(|kind| matches!(kind, NodeKind::Input(_))).collect(),
+            displays: outputs.iter().map(|(name, _)| displays[name]).collect(),
+            nodes,
+        }
+    }
+
+    /// Values of the outputs with the inputs set to `assignment`, flip-flops start from the
+    /// same state every time
+    fn run(&mut self, outputs: &[(String, u8)], assignment: &[(String, Value)]) -> Vec<Value> {
+        for &idx in &self.reset {
+            self.nodes[idx].kind = self.side.nodes[idx].kind.clone();
+        }
+
+        for (name, value) in assignment {
+            let Some(&idx) = self.inputs.get(name) else {
+                continue;
+            };
+            let node = &self.nodes[idx];
+            if let NodeKind::Input(input) = &node.kind {
+                input.set(value.mask(node.width));
+            }
+        }
+
+        self.evaluator.values.clear();
+        self.evaluator
+            .evaluate_scheduled(&self.nodes, self.side.dep_graph, &self.schedule, 0);
+
+        self.displays
+            .iter()
+            .zip(outputs)
+            .map(|(&idx, (_, width))| match &self.nodes[idx].kind {
+                NodeKind::Display(value) => value.get().mask(*width),
+                _ => Value::X,
+            })
+            .collect()
+    }
+}
+
+/// Simulates both circuits on `assignment`, a counterexample if any display differs
+fn compare(
+    benches: &mut [Bench; 2],
+    inputs: &[(String, u8)],
+    outputs: &[(String, u8)],
+    assignment: &[(String, Value)],
+) -> Option<Counterexample> {
+    let [a, b] = benches
+        .each_mut()
+        .map(|bench| bench.run(outputs, assignment));
+
+    let differing = outputs
+        .iter()
+        .zip(a.into_iter().zip(b))
+        .filter(|(_, (a, b))| a != b)
+        .map(|((name, width), (a, b))| {
+            let signal = Signal {
+                name: name.clone(),
+                width: *width,
+                value: a,
+            };
+            (signal, b)
+        })
+        .collect::<Vec<_>>();
+
+    (!differing.is_empty()).then(|| Counterexample {
+        inputs: inputs
+            .iter()
+            .zip(assignment)
+            .map(|((name, width), (_, value))| Signal {
+                name: name.clone(),
+                width: *width,
+                value: *value,
+            })
+            .collect(),
+        outputs: differing,
+    })
+}
+
+fn and(solver: &mut Solver, a: Lit, b: Lit) -> Lit {
+    let out = solver.new_var();
+    solver.add_clause(&[!out, a]);
+    solver.add_clause(&[!out, b]);
+    solver.add_clause(&[out, !a, !b]);
+    out
+}
+
+fn or(solver: &mut Solver, a: Lit, b: Lit) -> Lit {
+    !and(solver, !a, !b)
+}
+
+fn xor(solver: &mut Solver, a: Lit, b: Lit) -> Lit {
+    let out = solver.new_var();
+    solver.add_clause(&[!out, a, b]);
+    solver.add_clause(&[!out, !a, !b]);
+    solver.add_clause(&[out, !a, b]);
+    solver.add_clause(&[out, a, !b]);
+    out
+}
+
+/// Tseitin encoding of the gates driving a display, a literal per bit
+struct Encoder<'a> {
+    side: &'a Side<'a>,
+    solver: &'a mut Solver,
+    variables: &'a HashMap<String, Vec<Lit>>,
+    encoded: HashMap<NodeId, Vec<Lit>>,
+    // nodes on the path from the display, seeing one again means a loop
+    visiting: HashSet<NodeId>,
+}
+
+impl Encoder<'_> {
+    fn input(&mut self, node: &Node, pin: usize) -> Result<Vec<Lit>, String> {
+        let nodes = self.side.nodes;
+        let socket = node.inputs[pin].borrow();
+        let input = SocketRef {
+            node_id: node.id(),
+            socket_id: socket.id,
+        };
+
+        let label = || format!("{} of {}", socket.name, node.label(nodes));
+        let driver = match self.side.dep_graph.get(&input).map(Vec::as_slice) {
+            Some([driver]) => *driver,
+            None | Some([]) => return Err(format!("{} is not connected", label())),
+            Some(_) => return Err(format!("{} has several drivers", label())),
+        };
+
+        let driver = nodes
+            .iter()
+            .find(|node| node.id() == driver.node_id)
+            .ok_or_else(|| format!("{} is connected to a missing node", label()))?;
+
+        let bits = self.output(driver)?;
+        if bits.len() != socket.width as usize {
+            return Err(format!(
+                "{} is a {} bit pin driven by {} bits",
+                label(),
+                socket.width,
+                bits.len()
+            ));
+        }
+        Ok(bits)
+    }
+
+    fn output(&mut self, node: &Node) -> Result<Vec<Lit>, String> {
+        if let Some(bits) = self.encoded.get(&node.id()) {
+            return Ok(bits.clone());
+        }
+
+        let label = node.label(self.side.nodes);
+        if !self.visiting.insert(node.id()) {
+            return Err(format!("{label} is part of a feedback loop"));
+        }
+
+        let binary = |encoder: &mut Self, op: fn(&mut Solver, Lit, Lit) -> Lit| {
+            let (a, b) = (encoder.input(node, 0)?, encoder.input(node, 1)?);
+            let bits = a.into_iter().zip(b);
+            Ok::<_, String>(
+                bits.map(|(a, b)| op(encoder.solver, a, b))
+                    .collect::<Vec<_>>(),
+            )
+        };
+
+        let bits = match node.kind {
+            NodeKind::Input(_) => self.variables[&node.name].clone(),
+            NodeKind::Constant(value) => (0..node.width)
+                .map(|bit| {
+                    let lit = self.solver.new_var();
+                    let lit = match value.bit(bit) {
+                        Logic::One => lit,
+                        Logic::Zero => !lit,
+                        Logic::X | Logic::Z => {
+                            return Err(format!("{label} is neither 0 nor 1"));
+                        }
+                    };
+                    self.solver.add_clause(&[lit]);
+                    Ok(lit)
+                })
+                .collect::<Result<_, _>>()?,
+            NodeKind::Not => self.input(node, 0)?.into_iter().map(|a| !a).collect(),
+            NodeKind::And => binary(self, and)?,
+            NodeKind::NAnd => binary(self, and)?.into_iter().map(|a| !a).collect(),
+            NodeKind::Or => binary(self, or)?,
+            NodeKind::XOr => binary(self, xor)?,
+
+            _ => {
+                return Err(format!(
+                    "{label} can't be checked with SAT, only gates can be used with more than \
+                     {MAX_EXHAUSTIVE_BITS} input bits"
+                ));
+            }
+        };
+
+        self.visiting.remove(&node.id());
+        self.encoded.insert(node.id(), bits.clone());
+        Ok(bits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec2;
+
+    use super::*;
+    use crate::eval::build_dependency_graph;
+    use crate::{expr, synth};
+
+    fn circuit(equations: &str, nand_only: bool) -> (Vec<Node>, DependencyGraph) {
+        let equations = expr::parse_equations(equations).unwrap();
+        let (nodes, edges) =
+            synth::synthesize(&equations, nand_only, Vec2::ZERO, &mut IdAllocator::new());
+        (nodes, build_dependency_graph(edges))
+    }
+
+    /// Adds a display `q` driven by a gate with nothing on its input, which the solver can't
+    /// encode
+    fn dangling(nodes: &mut Vec<Node>, dep_graph: &mut DependencyGraph) {
+        let mut ids = IdAllocator::after(nodes);
+        let not = NodeKind::Not.build(Vec2::ZERO, &mut ids);
+        let mut display = NodeKind::Display(Default::default()).build(Vec2::ZERO, &mut ids);
+        display.name = "q".to_string();
+
+        let socket = |node: &Node, socket: &std::cell::RefCell<Socket>| SocketRef {
+            node_id: node.id(),
+            socket_id: socket.borrow().id,
+        };
+        dep_graph.insert(
+            socket(&display, &display.inputs[0]),
+            vec![socket(&not, &not.outputs[0])],
+        );
+        nodes.extend([not, display]);
+    }
+
+    #[test]
+    fn gates_are_checked_with_sat() {
+        let (a, a_graph) = circuit("y = a ^ b ^ c", false);
+        let (b, b_graph) = circuit("y = a ^ b ^ c", true);
+        let report = check(&a, &a_graph, &b, &b_graph).unwrap();
+        assert_eq!(report.method, Method::Sat);
+        assert!(report.counterexample.is_none());
+
+        let (c, c_graph) = circuit("y = a ^ b | c", true);
+        let report = check(&a, &a_graph, &c, &c_graph).unwrap();
+        assert!(report.counterexample.is_some());
+    }
+
+    #[test]
+    fn anything_else_is_simulated() {
+        let (mut a, mut a_graph) = circuit("y = a & b", false);
+        let (mut b, mut b_graph) = circuit("y = !(!a | !b)", true);
+        dangling(&mut a, &mut a_graph);
+        dangling(&mut b, &mut b_graph);
+
+        let report = check(&a, &a_graph, &b, &b_graph).unwrap();
+        assert_eq!(report.method, Method::Exhaustive);
+        assert!(report.counterexample.is_none());
+
+        let (mut c, mut c_graph) = circuit("y = a | b", true);
+        dangling(&mut c, &mut c_graph);
+        let report = check(&a, &a_graph, &c, &c_graph).unwrap();
+        let counterexample = report.counterexample.unwrap();
+        assert_eq!(counterexample.outputs[0].0.name, "y");
+    }
+
+    #[test]
+    fn constants_are_not_matched_as_inputs() {
+        let (a, a_graph) = circuit("y = a | 1", false);
+        let (b, b_graph) = circuit("y = a | !a", false);
+        let report = check(&a, &a_graph, &b, &b_graph).unwrap();
+        assert!(report.counterexample.is_none());
+    }
+
+    #[test]
+    fn pins_driven_by_another_width_are_rejected() {
+        let (mut a, a_graph) = circuit("y = a & b", false);
+        let input = a.iter_mut().find(|node| node.name == "a").unwrap();
+        input.width = 2;
+
+        let sides = [&a, &a].map(|nodes| Side {
+            nodes,
+            dep_graph: &a_graph,
+        });
+        let inputs = pins(&sides, |kind| matches!(kind, NodeKind::Input(_)), "input").unwrap();
+        let outputs = pins(
+            &sides,
+            |kind| matches!(kind, NodeKind::Display(_)),
+            "display",
+        )
+        .unwrap();
+
+        let err = solve(&sides, &inputs, &outputs).unwrap_err();
+        assert!(err.contains("is a 1 bit pin driven by 2 bits"), "{err}");
+    }
+}
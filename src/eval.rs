@@ -0,0 +1,363 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::logic::Value;
+use crate::wire::*;
+
+/// Maps every connected input socket to the output sockets driving it, only tri-state buffers
+/// share an input.
+pub type DependencyGraph = HashMap<SocketRef, Vec<SocketRef>>;
+
+pub fn build_dependency_graph(edges: impl IntoIterator<Item = Edge>) -> DependencyGraph {
+    let mut dep_graph = DependencyGraph::new();
+    for Edge { from, to } in edges {
+        dep_graph.entry(to).or_default().push(from);
+    }
+
+    dep_graph
+}
+
+/// Maps every output socket to the input sockets it drives, the reverse of a `DependencyGraph`
+pub type Fanout = HashMap<SocketRef, Vec<SocketRef>>;
+
+/// Number of passes over a circuit with feedback before it is considered oscillating
+const MAX_ITERATIONS: usize = 64;
+
+/// Order in which the nodes of a circuit are evaluated, only changes when the wiring does.
+#[derive(Debug, Clone, Default)]
+pub struct Schedule {
+    /// Indices into the node list, topologically sorted followed by the nodes stuck in a cycle
+    order: Vec<usize>,
+    cyclic: Vec<usize>,
+    /// Nodes with internal state that has to be clocked
+    stateful: Vec<usize>,
+}
+
+impl Schedule {
+    pub fn new(nodes: &[Node], dep_graph: &DependencyGraph) -> Self {
+        let (mut order, cyclic) = topological_order(nodes, dep_graph);
+        order.extend(&cyclic);
+
+        Self {
+            order,
+            cyclic,
+            stateful: (0..nodes.len())
+                .filter(|&idx| nodes[idx].kind.has_state())
+                .collect(),
+        }
+    }
+}
+
+/// Evaluates the whole circuit in topological order, computing every node exactly once per
+/// pass.
+///
+/// Values are kept between evaluations so gates inside a feedback loop (latches and the like)
+/// remember their previous output.
+#[derive(Debug, Clone, Default)]
+pub struct Evaluator {
+    pub values: HashMap<SocketRef, Value>,
+
+    /// Ids of the nodes that did not settle during the last evaluation
+    pub unstable: HashSet<NodeId>,
+}
+
+impl Evaluator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, socket: SocketRef) -> Option<Value> {
+        self.values.get(&socket).copied()
+    }
+
+    pub fn is_unstable(&self, node_id: NodeId) -> bool {
+        self.unstable.contains(&node_id)
+    }
+
+    /// Brings every socket value up to date, `tick` is the current simulation time used by clocks.
+    pub fn evaluate(&mut self, nodes: &[Node], dep_graph: &DependencyGraph, tick: u64) {
+        // forget about deleted nodes
+        let alive = nodes.iter().map(|node| node.id()).collect::<HashSet<_>>();
+        self.values
+            .retain(|socket, _| alive.contains(&socket.node_id));
+
+        self.evaluate_scheduled(nodes, dep_graph, &Schedule::new(nodes, dep_graph), tick);
+    }
+
+    /// Like `evaluate`, with a schedule kept by callers evaluating the same circuit over and over
+    pub fn evaluate_scheduled(
+        &mut self,
+        nodes: &[Node],
+        dep_graph: &DependencyGraph,
+        schedule: &Schedule,
+        tick: u64,
+    ) {
+        self.unstable.clear();
+        let mut flipping = vec![];
+
+        for _ in 0..MAX_ITERATIONS {
+            self.settle(nodes, dep_graph, schedule, tick);
+
+            flipping = self.clock(nodes, dep_graph, schedule, tick);
+            if flipping.is_empty() {
+                return;
+            }
+        }
+
+        // latches that keep flipping through their own feedback
+        self.unstable.extend(flipping);
+        self.settle(nodes, dep_graph, schedule, tick);
+    }
+
+    /// Propagates values through the combinational part of the circuit
+    pub fn settle(
+        &mut self,
+        nodes: &[Node],
+        dep_graph: &DependencyGraph,
+        schedule: &Schedule,
+        tick: u64,
+    ) {
+        if schedule.cyclic.is_empty() {
+            self.step(nodes, &schedule.order, dep_graph, tick);
+            return;
+        }
+
+        // gates in a loop start out low so the loop has something to settle from
+        for &idx in &schedule.cyclic {
+            let node = &nodes[idx];
+            for output in &node.outputs {
+                self.values
+                    .entry(SocketRef {
+                        node_id: node.id(),
+                        socket_id: output.borrow().id,
+                    })
+                    .or_default();
+            }
+        }
+
+        for _ in 0..MAX_ITERATIONS {
+            if self
+                .step(nodes, &schedule.order, dep_graph, tick)
+                .is_empty()
+            {
+                return;
+            }
+        }
+
+        // still changing, run a few more passes to find out who keeps flipping
+        for _ in 0..4 {
+            let changed = self.step(nodes, &schedule.order, dep_graph, tick);
+            self.unstable.extend(changed);
+        }
+    }
+
+    /// Updates the state of flip-flops, latches and chips from the settled values, returns the
+    /// ids of the nodes whose state changed.
+    pub fn clock(
+        &mut self,
+        nodes: &[Node],
+        dep_graph: &DependencyGraph,
+        schedule: &Schedule,
+        tick: u64,
+    ) -> Vec<NodeId> {
+        // sample every flip-flop before clocking any of them so they all see the same state
+        let inputs = schedule
+            .stateful
+            .iter()
+            .map(|&idx| self.inputs(&nodes[idx], dep_graph))
+            .collect::<Vec<_>>();
+
+        schedule
+            .stateful
+            .iter()
+            .zip(inputs)
+            .filter(|(idx, inputs)| nodes[**idx].kind.update(inputs, tick))
+            .map(|(&idx, _)| nodes[idx].id())
+            .collect()
+    }
+
+    /// Value currently on an input socket, Z when unconnected and the resolved value when it has
+    /// several drivers.
+    pub fn input(&self, input: SocketRef, dep_graph: &DependencyGraph) -> Value {
+        dep_graph
+            .get(&input)
+            .into_iter()
+            .flatten()
+            .map(|&output| self.get(output).unwrap_or(Value::X))
+            .fold(Value::Z, Value::resolve)
+    }
+
+    /// Values currently on the input pins of `node`
+    fn inputs(&self, node: &Node, dep_graph: &DependencyGraph) -> Vec<Value> {
+        node.inputs
+            .iter()
+            .map(|socket| {
+                self.input(
+                    SocketRef {
+                        node_id: node.id(),
+                        socket_id: socket.borrow().id,
+                    },
+                    dep_graph,
+                )
+            })
+            .collect()
+    }
+
+    /// Evaluates every node once in the given order, returns the ids of the nodes whose output
+    /// changed.
+    fn step(
+        &mut self,
+        nodes: &[Node],
+        order: &[usize],
+        dep_graph: &DependencyGraph,
+        tick: u64,
+    ) -> Vec<NodeId> {
+        let mut changed = vec![];
+
+        for &idx in order {
+            let node = &nodes[idx];
+            let inputs = self.inputs(node, dep_graph);
+            let values = node.kind.eval(&inputs, tick);
+
+            let mut node_changed = false;
+            for (output, value) in node.outputs.iter().zip(values) {
+                let output = output.borrow();
+                let value = value.mask(output.width);
+                let output = SocketRef {
+                    node_id: node.id(),
+                    socket_id: output.id,
+                };
+
+                node_changed |= self.values.insert(output, value) != Some(value);
+            }
+
+            if node_changed {
+                changed.push(node.id());
+            }
+        }
+
+        changed
+    }
+}
+
+/// Kahn's algorithm over the dependency graph, returns indices into `nodes`.
+///
+/// Nodes that are part of a cycle (or downstream of one) never reach an in-degree of zero, they
+/// are returned separately in their original order. Flip-flops and latches don't propagate their
+/// inputs directly so wires into them don't count as dependencies, which keeps counters and
+/// registers with feedback acyclic. Open latches still pass their input on within a single
+/// `Evaluator::evaluate`, which clocks them and settles again until nothing changes.
+fn topological_order(nodes: &[Node], dep_graph: &DependencyGraph) -> (Vec<usize>, Vec<usize>) {
+    let index_of = nodes
+        .iter()
+        .enumerate()
+        .map(|(idx, node)| (node.id(), idx))
+        .collect::<HashMap<_, _>>();
+
+    let mut in_degree = vec![0; nodes.len()];
+    let mut dependents = vec![vec![]; nodes.len()];
+
+    for (input, output) in dep_graph
+        .iter()
+        .flat_map(|(input, outputs)| outputs.iter().map(move |output| (input, output)))
+    {
+        let (Some(&to), Some(&from)) =
+            (index_of.get(&input.node_id), index_of.get(&output.node_id))
+        else {
+            continue;
+        };
+
+        if nodes[to].kind.is_sequential() {
+            continue;
+        }
+
+        in_degree[to] += 1;
+        dependents[from].push(to);
+    }
+
+    let mut queue = in_degree
+        .iter()
+        .enumerate()
+        .filter(|&(_, &degree)| degree == 0)
+        .map(|(idx, _)| idx)
+        .collect::<VecDeque<_>>();
+
+    let mut order = Vec::with_capacity(nodes.len());
+
+    while let Some(idx) = queue.pop_front() {
+        order.push(idx);
+
+        for &next in &dependents[idx] {
+            in_degree[next] -= 1;
+            if in_degree[next] == 0 {
+                queue.push_back(next);
+            }
+        }
+    }
+
+    let cyclic = (0..nodes.len()).filter(|&idx| in_degree[idx] > 0).collect();
+
+    (order, cyclic)
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec2;
+
+    use super::*;
+
+    fn wire(from: &Node, output: usize, to: &Node, input: usize) -> Edge {
+        Edge {
+            from: SocketRef {
+                node_id: from.id(),
+                socket_id: from.outputs[output].borrow().id,
+            },
+            to: SocketRef {
+                node_id: to.id(),
+                socket_id: to.inputs[input].borrow().id,
+            },
+        }
+    }
+
+    #[test]
+    fn open_latches_are_transparent_within_one_evaluation() {
+        let mut ids = IdAllocator::new();
+        let [d, en, first, second, display] = [
+            NodeKind::Input(Default::default()),
+            NodeKind::Input(Value::new(1).into()),
+            NodeKind::DLatch(Default::default()),
+            NodeKind::DLatch(Default::default()),
+            NodeKind::Display(Default::default()),
+        ]
+        .map(|kind| kind.build(Vec2::ZERO, &mut ids));
+
+        // two latches in a row, both open
+        let dep_graph = build_dependency_graph([
+            wire(&d, 0, &first, 0),
+            wire(&en, 0, &first, 1),
+            wire(&first, 0, &second, 0),
+            wire(&en, 0, &second, 1),
+            wire(&second, 0, &display, 0),
+        ]);
+        let nodes = vec![d, en, first, second, display];
+        let shown = |nodes: &[Node]| match &nodes[4].kind {
+            NodeKind::Display(value) => value.get(),
+            _ => unreachable!(),
+        };
+        let set = |nodes: &[Node], idx: usize, value: u64| {
+            if let NodeKind::Input(input) = &nodes[idx].kind {
+                input.set(Value::new(value));
+            }
+        };
+
+        let mut evaluator = Evaluator::new();
+        set(&nodes, 0, 1);
+        evaluator.evaluate(&nodes, &dep_graph, 0);
+        assert_eq!(shown(&nodes), Value::new(1));
+
+        // closed, they hold on to what they had
+        set(&nodes, 1, 0);
+        set(&nodes, 0, 0);
+        evaluator.evaluate(&nodes, &dep_graph, 0);
+        assert_eq!(shown(&nodes), Value::new(1));
+    }
+}
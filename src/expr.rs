@@ -0,0 +1,531 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::eval::DependencyGraph;
+use crate::wire::*;
+
+/// Boolean formula over named inputs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Const(bool),
+    Var(String),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Xor(Box<Expr>, Box<Expr>),
+}
+
+impl std::ops::Not for Expr {
+    type Output = Expr;
+
+    fn not(self) -> Expr {
+        Expr::Not(self.into())
+    }
+}
+
+impl std::ops::BitAnd for Expr {
+    type Output = Expr;
+
+    fn bitand(self, other: Expr) -> Expr {
+        Expr::And(self.into(), other.into())
+    }
+}
+
+impl std::ops::BitOr for Expr {
+    type Output = Expr;
+
+    fn bitor(self, other: Expr) -> Expr {
+        Expr::Or(self.into(), other.into())
+    }
+}
+
+impl std::ops::BitXor for Expr {
+    type Output = Expr;
+
+    fn bitxor(self, other: Expr) -> Expr {
+        Expr::Xor(self.into(), other.into())
+    }
+}
+
+impl Expr {
+    /// Binding strength in infix notation, higher binds tighter
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::Or(..) => 1,
+            Expr::Xor(..) => 2,
+            Expr::And(..) => 3,
+            Expr::Not(_) => 4,
+            Expr::Const(_) | Expr::Var(_) => 5,
+        }
+    }
+
+    pub fn eval(&self, var: &impl Fn(&str) -> bool) -> bool {
+        match self {
+            Expr::Const(value) => *value,
+            Expr::Var(name) => var(name),
+            Expr::Not(a) => !a.eval(var),
+            Expr::And(a, b) => a.eval(var) & b.eval(var),
+            Expr::Or(a, b) => a.eval(var) | b.eval(var),
+            Expr::Xor(a, b) => a.eval(var) ^ b.eval(var),
+        }
+    }
+
+    /// Number of constants, variables and operators in the formula
+    pub fn size(&self) -> usize {
+        match self {
+            Expr::Const(_) | Expr::Var(_) => 1,
+            Expr::Not(a) => 1 + a.size(),
+            Expr::And(a, b) | Expr::Or(a, b) | Expr::Xor(a, b) => 1 + a.size() + b.size(),
+        }
+    }
+
+    /// Names of the variables in order of first appearance
+    pub fn variables(&self) -> Vec<String> {
+        fn collect(expr: &Expr, vars: &mut Vec<String>) {
+            match expr {
+                Expr::Const(_) => {}
+                Expr::Var(name) => {
+                    if !vars.contains(name) {
+                        vars.push(name.clone());
+                    }
+                }
+                Expr::Not(a) => collect(a, vars),
+                Expr::And(a, b) | Expr::Or(a, b) | Expr::Xor(a, b) => {
+                    collect(a, vars);
+                    collect(b, vars);
+                }
+            }
+        }
+
+        let mut vars = vec![];
+        collect(self, &mut vars);
+        vars
+    }
+
+    /// Indices of the rows where the formula is true, with `vars[0]` as the most significant bit
+    pub fn minterms(&self, vars: &[String]) -> Vec<u32> {
+        (0..1u32 << vars.len())
+            .filter(|&row| {
+                self.eval(&|name| {
+                    let idx = vars.iter().position(|var| var == name).unwrap();
+                    row >> (vars.len() - 1 - idx) & 1 == 1
+                })
+            })
+            .collect()
+    }
+}
+
+impl std::fmt::Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // operands that bind looser than the operator need parentheses
+        let operand = |f: &mut std::fmt::Formatter<'_>, expr: &Expr| match expr.precedence()
+            < self.precedence()
+        {
+            true => write!(f, "({expr})"),
+            false => write!(f, "{expr}"),
+        };
+
+        let binary = |f: &mut std::fmt::Formatter<'_>, a: &Expr, op: &str, b: &Expr| {
+            operand(f, a)?;
+            write!(f, " {op} ")?;
+            operand(f, b)
+        };
+
+        match self {
+            Expr::Const(value) => write!(f, "{}", *value as u8),
+            Expr::Var(name) => write!(f, "{name}"),
+            Expr::Not(a) => {
+                write!(f, "!")?;
+                operand(f, a)
+            }
+            Expr::And(a, b) => binary(f, a, "&", b),
+            Expr::Or(a, b) => binary(f, a, "|", b),
+            Expr::Xor(a, b) => binary(f, a, "^", b),
+        }
+    }
+}
+
+/// Formulas extracted from a circuit are given up on past this size, gates feeding several
+/// others are written out once for every path to them
+pub const MAX_SIZE: usize = 1 << 12;
+
+/// Builds the formula computed by the Display node with id `display`, inputs are named after
+/// their Input nodes in a form `parse` accepts.
+///
+/// Only single bit gate networks can be expressed, anything with state, buses, feedback or a
+/// formula larger than `MAX_SIZE` is an error.
+pub fn extract(
+    nodes: &[Node],
+    dep_graph: &DependencyGraph,
+    display: NodeId,
+) -> Result<Expr, String> {
+    let node = nodes
+        .iter()
+        .find(|node| node.id() == display)
+        .ok_or_else(|| format!("no node with id {display}"))?;
+
+    Extractor {
+        nodes,
+        dep_graph,
+        visiting: HashSet::new(),
+        exprs: HashMap::new(),
+    }
+    .input(node, 0)
+}
+
+struct Extractor<'a> {
+    nodes: &'a [Node],
+    dep_graph: &'a DependencyGraph,
+    // nodes on the path from the display, seeing one again means a loop
+    visiting: HashSet<NodeId>,
+    // formulas of the nodes seen so far, reached again through every path to them
+    exprs: HashMap<NodeId, Expr>,
+}
+
+impl Extractor<'_> {
+    /// Formula for whatever drives input pin `pin` of `node`
+    fn input(&mut self, node: &Node, pin: usize) -> Result<Expr, String> {
+        let socket = node.inputs[pin].borrow();
+        let input = SocketRef {
+            node_id: node.id(),
+            socket_id: socket.id,
+        };
+
+        let label = || format!("{} of {}", socket.name, node.label(self.nodes));
+        let driver = match self.dep_graph.get(&input).map(Vec::as_slice) {
+            Some([driver]) => *driver,
+            None | Some([]) => return Err(format!("{} is not connected", label())),
+            Some(_) => return Err(format!("{} has several drivers", label())),
+        };
+
+        let node = self
+            .nodes
+            .iter()
+            .find(|node| node.id() == driver.node_id)
+            .ok_or_else(|| format!("{} is connected to a missing node", label()))?;
+
+        self.output(node)
+    }
+
+    /// Formula for the output of `node`
+    fn output(&mut self, node: &Node) -> Result<Expr, String> {
+        if let Some(expr) = self.exprs.get(&node.id()) {
+            return Ok(expr.clone());
+        }
+
+        let label = node.label(self.nodes);
+
+        if node.width > 1 {
+            return Err(format!("{label} is a bus"));
+        }
+
+        if !self.visiting.insert(node.id()) {
+            return Err(format!("{label} is part of a feedback loop"));
+        }
+
+        let expr = match node.kind {
+            NodeKind::Input(_) => Expr::Var(variable(node, self.nodes)),
+            NodeKind::Constant(value) => match value.to_bool() {
+                Some(level) => Expr::Const(level),
+                None => return Err(format!("{label} is neither 0 nor 1")),
+            },
+            NodeKind::Display(_) => self.input(node, 0)?,
+            NodeKind::Not => !self.input(node, 0)?,
+            NodeKind::And => self.input(node, 0)? & self.input(node, 1)?,
+            NodeKind::NAnd => !(self.input(node, 0)? & self.input(node, 1)?),
+            NodeKind::Or => self.input(node, 0)? | self.input(node, 1)?,
+            NodeKind::XOr => self.input(node, 0)? ^ self.input(node, 1)?,
+
+            _ => return Err(format!("{label} can't be expressed as a formula")),
+        };
+
+        if expr.size() > MAX_SIZE {
+            return Err(format!(
+                "the formula for {label} has more than {MAX_SIZE} terms"
+            ));
+        }
+
+        self.visiting.remove(&node.id());
+        self.exprs.insert(node.id(), expr.clone());
+        Ok(expr)
+    }
+}
+
+/// Name of the variable standing for the Input node `node`, its name made into an identifier
+/// and told apart from other inputs by the id if it has to be
+fn variable(node: &Node, nodes: &[Node]) -> String {
+    let identifier = |name: &str| {
+        let mut identifier = name
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect::<String>();
+        if !identifier.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+            identifier.insert(0, '_');
+        }
+        identifier
+    };
+
+    let name = identifier(&node.name);
+    let shared = nodes
+        .iter()
+        .filter(|other| matches!(other.kind, NodeKind::Input(_)))
+        .filter(|other| identifier(&other.name) == name)
+        .count();
+
+    match shared {
+        0 | 1 => name,
+        _ => format!("{name}_{}", node.id()),
+    }
+}
+
+/// Parses assignments like `out = (a & b) | !c`, one per line or separated by `;`.
+///
+/// `!` (or `~`) binds tightest, then `&`, `^` and `|`; `0` and `1` are constants.
+pub fn parse_equations(text: &str) -> Result<Vec<(String, Expr)>, String> {
+    let mut equations: Vec<(String, Expr)> = vec![];
+
+    for equation in text.split([';', '\n']).map(str::trim) {
+        if equation.is_empty() {
+            continue;
+        }
+
+        let (name, expr) = equation
+            .split_once('=')
+            .ok_or_else(|| format!("expected NAME = EXPRESSION, got `{equation}`"))?;
+
+        let name = name.trim();
+        if !is_identifier(name) {
+            return Err(format!("`{name}` is not a valid output name"));
+        }
+
+        if equations.iter().any(|(other, _)| other == name) {
+            return Err(format!("{name} is assigned more than once"));
+        }
+
+        let expr = parse(expr).map_err(|err| format!("{name}: {err}"))?;
+        equations.push((name.to_string(), expr));
+    }
+
+    Ok(equations)
+}
+
+/// Parses a single infix expression, the inverse of formatting an `Expr`
+pub fn parse(text: &str) -> Result<Expr, String> {
+    let mut parser = Parser {
+        tokens: tokenize(text)?,
+        pos: 0,
+    };
+
+    let expr = parser.or()?;
+    match parser.tokens.get(parser.pos) {
+        None => Ok(expr),
+        Some(token) => Err(format!("unexpected {token}")),
+    }
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Name(String),
+    Const(bool),
+    Op(char),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Name(name) => write!(f, "`{name}`"),
+            Token::Const(value) => write!(f, "`{}`", *value as u8),
+            Token::Op(op) => write!(f, "`{op}`"),
+        }
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            _ if c.is_whitespace() => {}
+            '&' | '|' | '^' | '!' | '(' | ')' => tokens.push(Token::Op(c)),
+            '~' => tokens.push(Token::Op('!')),
+            '0' => tokens.push(Token::Const(false)),
+            '1' => tokens.push(Token::Const(true)),
+
+            _ if c.is_ascii_alphabetic() || c == '_' => {
+                let mut name = c.to_string();
+                while let Some(&c) = chars.peek()
+                    && (c.is_ascii_alphanumeric() || c == '_')
+                {
+                    name.push(c);
+                    chars.next();
+                }
+
+                tokens.push(Token::Name(name));
+            }
+
+            _ => return Err(format!("unexpected character `{c}`")),
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Recursive descent, one method per precedence level
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn eat(&mut self, op: char) -> bool {
+        let found = self.tokens.get(self.pos) == Some(&Token::Op(op));
+        if found {
+            self.pos += 1;
+        }
+
+        found
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.xor()?;
+        while self.eat('|') {
+            expr = expr | self.xor()?;
+        }
+
+        Ok(expr)
+    }
+
+    fn xor(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.eat('^') {
+            expr = expr ^ self.and()?;
+        }
+
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.not()?;
+        while self.eat('&') {
+            expr = expr & self.not()?;
+        }
+
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<Expr, String> {
+        if self.eat('!') {
+            return Ok(!self.not()?);
+        }
+
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or("unexpected end of the expression")?;
+        self.pos += 1;
+
+        match token {
+            Token::Name(name) => Ok(Expr::Var(name)),
+            Token::Const(value) => Ok(Expr::Const(value)),
+            Token::Op('(') => {
+                let expr = self.or()?;
+                match self.eat(')') {
+                    true => Ok(expr),
+                    false => Err("missing `)`".to_string()),
+                }
+            }
+
+            _ => Err(format!("unexpected {token}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec2;
+
+    use super::*;
+    use crate::circuit::Circuit;
+
+    fn wire(circuit: &mut Circuit, from: &Node, to: &Node, pin: usize) {
+        circuit.connect(Edge {
+            from: SocketRef {
+                node_id: from.id(),
+                socket_id: from.outputs[0].borrow().id,
+            },
+            to: SocketRef {
+                node_id: to.id(),
+                socket_id: to.inputs[pin].borrow().id,
+            },
+        });
+    }
+
+    /// Display fed by `levels` AND gates, each with both inputs on the one before
+    fn doubling(levels: usize) -> (Circuit, NodeId) {
+        let mut ids = IdAllocator::new();
+        let mut circuit = Circuit::new();
+        let mut last = NodeKind::Input(Default::default()).build(Vec2::ZERO, &mut ids);
+
+        for _ in 0..levels {
+            let gate = NodeKind::And.build(Vec2::ZERO, &mut ids);
+            wire(&mut circuit, &last, &gate, 0);
+            wire(&mut circuit, &last, &gate, 1);
+            circuit.push(std::mem::replace(&mut last, gate));
+        }
+
+        let display = NodeKind::Display(Default::default()).build(Vec2::ZERO, &mut ids);
+        wire(&mut circuit, &last, &display, 0);
+        let id = display.id();
+        circuit.extend([last, display]);
+        (circuit, id)
+    }
+
+    #[test]
+    fn shared_gates_are_extracted_once() {
+        let (circuit, display) = doubling(10);
+        let expr = extract(&circuit, circuit.dependency_graph(), display).unwrap();
+        assert_eq!(expr.size(), (1 << 11) - 1);
+
+        let (circuit, display) = doubling(64);
+        let err = extract(&circuit, circuit.dependency_graph(), display).unwrap_err();
+        assert!(err.contains("more than"), "{err}");
+    }
+
+    #[test]
+    fn variables_parse_back() {
+        let mut ids = IdAllocator::new();
+        let mut circuit = Circuit::new();
+        let names = ["a", "a", "carry in", "2nd"];
+        let inputs = names.map(|name| {
+            let mut input = NodeKind::Input(Default::default()).build(Vec2::ZERO, &mut ids);
+            input.name = name.to_string();
+            input
+        });
+
+        let mut last = inputs[0].clone();
+        for input in &inputs[1..] {
+            let gate = NodeKind::Or.build(Vec2::ZERO, &mut ids);
+            wire(&mut circuit, &last, &gate, 0);
+            wire(&mut circuit, input, &gate, 1);
+            circuit.push(std::mem::replace(&mut last, gate));
+        }
+        let display = NodeKind::Display(Default::default()).build(Vec2::ZERO, &mut ids);
+        wire(&mut circuit, &last, &display, 0);
+        let id = display.id();
+        circuit.extend([last, display]);
+        circuit.extend(inputs.into_iter().skip(1));
+
+        let expr = extract(&circuit, circuit.dependency_graph(), id).unwrap();
+        assert_eq!(expr.variables().len(), 4);
+        assert_eq!(parse(&expr.to_string()), Ok(expr));
+    }
+}
@@ -0,0 +1,284 @@
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::Path;
+
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+use crate::chip::Chip;
+use crate::route::{Route, WireStyle};
+use crate::wire::*;
+
+const FORMAT_VERSION: u32 = 1;
+
+/// Routes of the wires that have one, by wire
+pub type Routes = HashMap<Edge, Route>;
+
+/// On-disk representation of a circuit, stored as pretty printed JSON
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CircuitFile {
+    pub version: u32,
+    pub nodes: Vec<NodeFile>,
+    pub edges: Vec<Edge>,
+
+    /// Chips shown in the palette, instances carry their own copy
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub chips: Vec<Chip>,
+
+    /// Wires that aren't drawn as plain curves
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub routes: Vec<RouteFile>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NodeFile {
+    pub id: NodeId,
+    pub name: String,
+    pub kind: NodeKind,
+    pub position: [f32; 2],
+    #[serde(default = "single_bit")]
+    pub width: u8,
+    pub inputs: Vec<SocketFile>,
+    pub outputs: Vec<SocketFile>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SocketFile {
+    pub id: SocketId,
+    pub name: String,
+    #[serde(default = "single_bit")]
+    pub width: u8,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RouteFile {
+    pub edge: Edge,
+    pub style: WireStyle,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub waypoints: Vec<[f32; 2]>,
+}
+
+/// Circuit recreated from a file
+#[derive(Debug)]
+pub struct Contents {
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+    pub routes: Routes,
+    /// Id every node was given by the id it had in the file
+    pub node_ids: HashMap<NodeId, NodeId>,
+}
+
+/// Files saved before buses existed only have single bit sockets
+fn single_bit() -> u8 {
+    1
+}
+
+impl CircuitFile {
+    pub fn new(nodes: &[Node], edges: impl IntoIterator<Item = Edge>) -> Self {
+        let sockets = |sockets: &[std::cell::RefCell<Socket>]| {
+            sockets
+                .iter()
+                .map(|socket| {
+                    let socket = socket.borrow();
+                    SocketFile {
+                        id: socket.id,
+                        name: socket.name.clone(),
+                        width: socket.width,
+                    }
+                })
+                .collect()
+        };
+
+        Self {
+            version: FORMAT_VERSION,
+            nodes: nodes
+                .iter()
+                .map(|node| {
+                    let position = *node.position.borrow();
+                    NodeFile {
+                        id: node.id(),
+                        name: node.name.clone(),
+                        kind: node.kind.clone(),
+                        position: [position.x, position.y],
+                        width: node.width,
+                        inputs: sockets(&node.inputs),
+                        outputs: sockets(&node.outputs),
+                    }
+                })
+                .collect(),
+            edges: edges.into_iter().collect(),
+            chips: vec![],
+            routes: vec![],
+        }
+    }
+
+    /// Stores the routes of the wires in the file, plain curves are left out
+    pub fn set_routes(&mut self, routes: &Routes) {
+        self.routes = self
+            .edges
+            .iter()
+            .filter_map(|edge| {
+                let route = routes.get(edge).filter(|route| !route.is_default())?;
+                Some(RouteFile {
+                    edge: *edge,
+                    style: route.style,
+                    waypoints: route
+                        .waypoints
+                        .iter()
+                        .map(|point| point.to_array())
+                        .collect(),
+                })
+            })
+            .collect();
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// Reads a circuit from the text of a file, or a fragment copied to the clipboard
+    pub fn parse(text: &str) -> io::Result<Self> {
+        let file: Self = serde_json::from_str(text)?;
+
+        if file.version > FORMAT_VERSION {
+            return Err(io::Error::other(format!(
+                "file format version {} is newer than the supported version {FORMAT_VERSION}",
+                file.version
+            )));
+        }
+
+        Ok(file)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        std::fs::write(path, self.to_text()?)
+    }
+
+    pub fn to_text(&self) -> io::Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Recreates the nodes with ids from `ids` so they can't collide with the circuit they're
+    /// added to, the edges and routes are remapped accordingly. Edges referring to unknown
+    /// sockets are dropped.
+    pub fn build(self, ids: &mut IdAllocator) -> Contents {
+        self.renumber(Some(ids))
+    }
+
+    /// Recreates the nodes keeping the ids stored in the file, see `IdAllocator::after`. Fails
+    /// if two nodes share an id.
+    pub fn into_circuit(self) -> io::Result<Contents> {
+        let mut seen = HashSet::new();
+        if let Some(node) = self.nodes.iter().find(|node| !seen.insert(node.id)) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("more than one node has id {}", node.id),
+            ));
+        }
+
+        Ok(self.renumber(None))
+    }
+
+    /// Gives every node and socket a new id from `ids`, or keeps them if there is none
+    fn renumber(self, mut ids: Option<&mut IdAllocator>) -> Contents {
+        let mut remap = HashMap::new();
+        let mut node_ids = HashMap::new();
+
+        let nodes = self
+            .nodes
+            .into_iter()
+            .map(|node| {
+                let id = ids.as_mut().map_or(node.id, |ids| ids.node());
+                node_ids.insert(node.id, id);
+
+                let mut sockets = |sockets: Vec<SocketFile>, kind: SocketKind| {
+                    sockets
+                        .into_iter()
+                        .map(|socket| {
+                            let socket_id = ids.as_mut().map_or(socket.id, |ids| ids.socket());
+                            remap.insert(
+                                SocketRef {
+                                    node_id: node.id,
+                                    socket_id: socket.id,
+                                },
+                                SocketRef {
+                                    node_id: id,
+                                    socket_id,
+                                },
+                            );
+
+                            Socket {
+                                name: socket.name,
+                                id: socket_id,
+                                width: socket.width,
+                                kind,
+                                absolute_position: None,
+                            }
+                            .into()
+                        })
+                        .collect::<Vec<_>>()
+                };
+
+                let inputs = sockets(node.inputs, SocketKind::Input);
+                let outputs = sockets(node.outputs, SocketKind::Output);
+
+                Node::restore(
+                    id,
+                    node.name,
+                    Vec2::from(node.position),
+                    node.width,
+                    node.kind,
+                    inputs,
+                    outputs,
+                )
+            })
+            .collect();
+
+        let remap_edge = |Edge { from, to }: Edge| {
+            Some(Edge {
+                from: *remap.get(&from)?,
+                to: *remap.get(&to)?,
+            })
+        };
+
+        let edges = self.edges.into_iter().filter_map(remap_edge).collect();
+        let routes = self
+            .routes
+            .into_iter()
+            .filter_map(|route| {
+                let waypoints = route.waypoints.into_iter().map(Vec2::from).collect();
+                Some((
+                    remap_edge(route.edge)?,
+                    Route {
+                        style: route.style,
+                        waypoints,
+                    },
+                ))
+            })
+            .collect();
+
+        Contents {
+            nodes,
+            edges,
+            routes,
+            node_ids,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logic::Value;
+
+    #[test]
+    fn duplicate_node_ids_are_rejected() {
+        let mut ids = IdAllocator::new();
+        let node = NodeKind::Constant(Value::new(1)).build(Vec2::ZERO, &mut ids);
+        let mut file = CircuitFile::new(&[node], []);
+        let copy = CircuitFile::parse(&file.to_text().unwrap()).unwrap();
+        file.nodes.extend(copy.nodes);
+
+        assert!(file.into_circuit().is_err());
+    }
+}
@@ -0,0 +1,363 @@
+use std::collections::HashSet;
+
+use glam::Vec2;
+use illogical::circuit::Circuit;
+use illogical::file::Routes;
+use illogical::logic::Value;
+use illogical::route::Route;
+use illogical::wire::*;
+
+/// Entries kept before the oldest ones are dropped
+const MAX_ENTRIES: usize = 256;
+
+/// One step of an edit, nodes and wires with the index they have in the circuit while added
+#[derive(Debug, Clone)]
+enum Change {
+    AddNodes(Vec<(usize, Node)>),
+    RemoveNodes(Vec<(usize, Node)>),
+    Connect(Vec<(usize, Edge)>),
+    Disconnect(Vec<(usize, Edge)>),
+    /// Node changed in place, before and after
+    Update(Box<Node>, Box<Node>),
+    /// Wires given another route, with the route before and after
+    Route(Vec<(Edge, Route, Route)>),
+}
+
+impl Change {
+    /// Makes the change again, or takes it back if `forward` is false
+    fn apply(&self, nodes: &mut Circuit, routes: &mut Routes, forward: bool) {
+        match (self, forward) {
+            // indices are ascending, inserting from the front puts every node back in its place
+            (Change::AddNodes(added), true) | (Change::RemoveNodes(added), false) => {
+                for (idx, node) in added {
+                    nodes.insert(*idx, node.clone());
+                }
+            }
+            (Change::AddNodes(removed), false) | (Change::RemoveNodes(removed), true) => {
+                let ids = removed
+                    .iter()
+                    .map(|(_, node)| node.id())
+                    .collect::<HashSet<_>>();
+                nodes.retain(|node| !ids.contains(&node.id()));
+            }
+            (Change::Connect(added), true) | (Change::Disconnect(added), false) => {
+                for (idx, edge) in added {
+                    nodes.connect_at(*idx, *edge);
+                }
+            }
+            (Change::Connect(removed), false) | (Change::Disconnect(removed), true) => {
+                let edges = removed
+                    .iter()
+                    .map(|(_, edge)| *edge)
+                    .collect::<HashSet<_>>();
+                nodes.retain_edges(|edge| !edges.contains(&edge));
+            }
+            (Change::Update(before, after), _) => {
+                let node = if forward { after } else { before };
+                nodes.replace((**node).clone());
+            }
+            (Change::Route(changed), _) => {
+                for (edge, before, after) in changed {
+                    set_route(routes, *edge, if forward { after } else { before });
+                }
+            }
+        }
+    }
+}
+
+/// Changes to the nodes and wires of the circuit, made through the edit so it can take them
+/// back in reverse
+#[derive(Debug, Clone, Default)]
+pub struct Edit {
+    changes: Vec<Change>,
+}
+
+impl Edit {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn add_nodes(&mut self, nodes: &mut Circuit, added: impl IntoIterator<Item = Node>) {
+        let added = added
+            .into_iter()
+            .map(|node| {
+                let idx = nodes.len();
+                nodes.push(node.clone());
+                (idx, node)
+            })
+            .collect::<Vec<_>>();
+
+        if !added.is_empty() {
+            self.changes.push(Change::AddNodes(added));
+        }
+    }
+
+    /// Removes the nodes `remove` picks along with their wires
+    pub fn remove_nodes(&mut self, nodes: &mut Circuit, remove: impl Fn(NodeId) -> bool) {
+        self.retain_edges(nodes, |edge| {
+            !remove(edge.from.node_id) && !remove(edge.to.node_id)
+        });
+
+        let removed = nodes.extract_if(|node| remove(node.id()));
+        if !removed.is_empty() {
+            self.changes.push(Change::RemoveNodes(removed));
+        }
+    }
+
+    pub fn connect(&mut self, nodes: &mut Circuit, added: impl IntoIterator<Item = Edge>) {
+        let added = added
+            .into_iter()
+            .map(|edge| {
+                let idx = nodes.edges().len();
+                nodes.connect(edge);
+                (idx, edge)
+            })
+            .collect::<Vec<_>>();
+
+        if !added.is_empty() {
+            self.changes.push(Change::Connect(added));
+        }
+    }
+
+    pub fn retain_edges(&mut self, nodes: &mut Circuit, keep: impl FnMut(Edge) -> bool) {
+        let removed = nodes.retain_edges(keep);
+        if !removed.is_empty() {
+            self.changes.push(Change::Disconnect(removed));
+        }
+    }
+
+    /// Gives the wires new routes, curves without waypoints aren't stored
+    pub fn route(&mut self, routes: &mut Routes, changed: impl IntoIterator<Item = (Edge, Route)>) {
+        let changed = changed
+            .into_iter()
+            .map(|(edge, route)| {
+                let before = routes.get(&edge).cloned().unwrap_or_default();
+                set_route(routes, edge, &route);
+                (edge, before, route)
+            })
+            .collect::<Vec<_>>();
+
+        if !changed.is_empty() {
+            self.changes.push(Change::Route(changed));
+        }
+    }
+
+    /// Changes the node with id `id` in place, `change` returns whether it changed anything
+    pub fn update(
+        &mut self,
+        nodes: &mut Circuit,
+        id: NodeId,
+        change: impl FnOnce(&mut Node) -> bool,
+    ) -> bool {
+        let Some(node) = nodes.get_mut(id) else {
+            return false;
+        };
+
+        let before = node.clone();
+        if !change(node) {
+            return false;
+        }
+
+        self.changes
+            .push(Change::Update(before.into(), node.clone().into()));
+        true
+    }
+
+    fn apply(&self, nodes: &mut Circuit, routes: &mut Routes, forward: bool) {
+        match forward {
+            true => self
+                .changes
+                .iter()
+                .for_each(|change| change.apply(nodes, routes, true)),
+            false => self
+                .changes
+                .iter()
+                .rev()
+                .for_each(|change| change.apply(nodes, routes, false)),
+        }
+    }
+}
+
+fn set_route(routes: &mut Routes, edge: Edge, route: &Route) {
+    match route.is_default() {
+        true => routes.remove(&edge),
+        false => routes.insert(edge, route.clone()),
+    };
+}
+
+#[derive(Debug, Clone)]
+pub enum Command {
+    Edit(Edit),
+    /// Nodes dragged together, with their positions before and after
+    Move {
+        nodes: Vec<NodeId>,
+        from: Vec<Vec2>,
+        to: Vec<Vec2>,
+    },
+    SetValue {
+        node: NodeId,
+        from: Value,
+        to: Value,
+    },
+    /// Period and duty cycle of a clock
+    SetClock {
+        node: NodeId,
+        from: (u32, f32),
+        to: (u32, f32),
+    },
+    /// Style and waypoints of a wire
+    Route {
+        edge: Edge,
+        from: Route,
+        to: Route,
+    },
+}
+
+impl Command {
+    /// Id of the node a value change applies to
+    fn node(&self) -> Option<NodeId> {
+        match self {
+            Command::Edit(_) | Command::Move { .. } | Command::Route { .. } => None,
+            Command::SetValue { node, .. } | Command::SetClock { node, .. } => Some(*node),
+        }
+    }
+
+    /// Takes over the end state of `next`, a change of the same kind to the same nodes
+    fn merge(&mut self, next: Command) -> Result<(), Command> {
+        match (self, next) {
+            (
+                Command::Move { nodes, to, .. },
+                Command::Move {
+                    nodes: ids,
+                    to: next,
+                    ..
+                },
+            ) if *nodes == ids => {
+                *to = next;
+            }
+            (
+                Command::SetValue { node, to, .. },
+                Command::SetValue {
+                    node: id, to: next, ..
+                },
+            ) if *node == id => {
+                *to = next;
+            }
+            (
+                Command::SetClock { node, to, .. },
+                Command::SetClock {
+                    node: id, to: next, ..
+                },
+            ) if *node == id => {
+                *to = next;
+            }
+            (_, next) => return Err(next),
+        }
+
+        Ok(())
+    }
+
+    /// Redoes the command, or undoes it if `forward` is false
+    pub fn apply(&self, nodes: &mut Circuit, routes: &mut Routes, forward: bool) {
+        match self {
+            Command::Edit(edit) => return edit.apply(nodes, routes, forward),
+            Command::Move {
+                nodes: ids,
+                from,
+                to,
+            } => {
+                let positions = if forward { to } else { from };
+                for (id, position) in ids.iter().zip(positions) {
+                    if let Some(node) = nodes.get(*id) {
+                        *node.position.borrow_mut() = *position;
+                    }
+                }
+                return;
+            }
+            Command::Route { edge, from, to } => {
+                set_route(routes, *edge, if forward { to } else { from });
+                return;
+            }
+            _ => {}
+        }
+
+        let node = self.node().and_then(|id| nodes.get(id));
+        let Some(node) = node else {
+            return;
+        };
+
+        match (self, &node.kind) {
+            (Command::SetValue { from, to, .. }, NodeKind::Input(value)) => {
+                value.set(if forward { *to } else { *from });
+            }
+            (Command::SetClock { from, to, .. }, NodeKind::Clock { period, duty }) => {
+                let (ticks, high) = if forward { *to } else { *from };
+                period.set(ticks);
+                duty.set(high);
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub label: String,
+    pub command: Command,
+}
+
+/// Undo and redo stacks of the circuit on the canvas
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    pub done: Vec<Entry>,
+    /// Most recently undone last
+    pub undone: Vec<Entry>,
+    // the last entry won't take in further changes to its node
+    sealed: bool,
+}
+
+impl History {
+    /// Records a command that was already carried out
+    pub fn push(&mut self, label: String, command: Command) {
+        if matches!(&command, Command::Edit(edit) if edit.is_empty()) {
+            return;
+        }
+
+        self.undone.clear();
+
+        // drags and slider changes end up as a single entry
+        let command = match self.done.last_mut() {
+            Some(last) if !self.sealed => match last.command.merge(command) {
+                Ok(()) => return,
+                Err(command) => command,
+            },
+            _ => command,
+        };
+
+        self.done.push(Entry { label, command });
+        self.sealed = false;
+
+        if self.done.len() > MAX_ENTRIES {
+            self.done.remove(0);
+        }
+    }
+
+    /// Ends the current drag, the next change starts a new entry
+    pub fn seal(&mut self) {
+        self.sealed = true;
+    }
+
+    pub fn undo(&mut self) -> Option<&Command> {
+        let entry = self.done.pop()?;
+        self.undone.push(entry);
+        self.sealed = true;
+        self.undone.last().map(|entry| &entry.command)
+    }
+
+    pub fn redo(&mut self) -> Option<&Command> {
+        let entry = self.undone.pop()?;
+        self.done.push(entry);
+        self.sealed = true;
+        self.done.last().map(|entry| &entry.command)
+    }
+}
@@ -0,0 +1,18 @@
+//! Circuit model and simulation, none of it depends on raylib or imgui so it can be used
+//! headless.
+
+pub mod chip;
+pub mod circuit;
+pub mod equivalence;
+pub mod eval;
+pub mod expr;
+pub mod file;
+pub mod logic;
+pub mod minimize;
+pub mod route;
+pub mod sat;
+pub mod simulation;
+pub mod subnetwork;
+pub mod synth;
+pub mod truth_table;
+pub mod wire;
@@ -0,0 +1,226 @@
+use serde::{Deserialize, Serialize};
+
+/// Level of a single line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Logic {
+    Zero,
+    One,
+    /// Driven, but the level is unknown (conflicting drivers, undriven gate inputs, ...)
+    X,
+    /// Not driven at all
+    Z,
+}
+
+impl From<bool> for Logic {
+    fn from(value: bool) -> Self {
+        match value {
+            true => Logic::One,
+            false => Logic::Zero,
+        }
+    }
+}
+
+impl std::fmt::Display for Logic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Logic::Zero => "0",
+                Logic::One => "1",
+                Logic::X => "X",
+                Logic::Z => "Z",
+            }
+        )
+    }
+}
+
+/// Four-state value of a wire up to 64 bits wide, stored as bit planes.
+///
+/// A bit set in `x` is unknown, a bit set in `z` is not driven, `bits` holds the level of the
+/// remaining ones.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Value {
+    pub bits: u64,
+    pub x: u64,
+    pub z: u64,
+}
+
+impl From<Logic> for Value {
+    fn from(logic: Logic) -> Self {
+        match logic {
+            Logic::Zero => Value::new(0),
+            Logic::One => Value::new(1),
+            Logic::X => Value::X,
+            Logic::Z => Value::Z,
+        }
+    }
+}
+
+impl std::ops::Not for Value {
+    type Output = Value;
+
+    fn not(self) -> Self {
+        Self::known(self.zeros(), self.ones())
+    }
+}
+
+/// A low input forces the output low even if the other one is unknown
+impl std::ops::BitAnd for Value {
+    type Output = Value;
+
+    fn bitand(self, other: Self) -> Self {
+        Self::known(self.ones() & other.ones(), self.zeros() | other.zeros())
+    }
+}
+
+/// A high input forces the output high even if the other one is unknown
+impl std::ops::BitOr for Value {
+    type Output = Value;
+
+    fn bitor(self, other: Self) -> Self {
+        Self::known(self.ones() | other.ones(), self.zeros() & other.zeros())
+    }
+}
+
+impl std::ops::BitXor for Value {
+    type Output = Value;
+
+    fn bitxor(self, other: Self) -> Self {
+        let known = (self.ones() | self.zeros()) & (other.ones() | other.zeros());
+        let bits = (self.bits ^ other.bits) & known;
+        Self::known(bits, !bits & known)
+    }
+}
+
+impl Value {
+    pub const X: Value = Value {
+        bits: 0,
+        x: u64::MAX,
+        z: 0,
+    };
+
+    pub const Z: Value = Value {
+        bits: 0,
+        x: 0,
+        z: u64::MAX,
+    };
+
+    pub fn new(bits: u64) -> Self {
+        Self { bits, x: 0, z: 0 }
+    }
+
+    /// Builds a value from the bits known to be high and low, everything else is X
+    fn known(ones: u64, zeros: u64) -> Self {
+        Self {
+            bits: ones,
+            x: !(ones | zeros),
+            z: 0,
+        }
+    }
+
+    fn ones(self) -> u64 {
+        self.bits & !(self.x | self.z)
+    }
+
+    fn zeros(self) -> u64 {
+        !(self.bits | self.x | self.z)
+    }
+
+    /// Clears everything above the lowest `width` bits
+    pub fn mask(self, width: u8) -> Self {
+        let mask = crate::wire::mask(width);
+        Self {
+            bits: self.bits & mask,
+            x: self.x & mask,
+            z: self.z & mask,
+        }
+    }
+
+    /// `true` if none of the lowest `width` bits are X or Z
+    pub fn is_known(self, width: u8) -> bool {
+        (self.x | self.z) & crate::wire::mask(width) == 0
+    }
+
+    pub fn bit(self, bit: u8) -> Logic {
+        let set = |plane: u64| plane >> bit & 1 == 1;
+
+        if set(self.z) {
+            Logic::Z
+        } else if set(self.x) {
+            Logic::X
+        } else {
+            set(self.bits).into()
+        }
+    }
+
+    pub fn set_bit(&mut self, bit: u8, logic: Logic) {
+        let value = Value::from(logic);
+        let set = |plane: &mut u64, from: u64| *plane = *plane & !(1 << bit) | (from & 1) << bit;
+
+        set(&mut self.bits, value.bits);
+        set(&mut self.x, value.x);
+        set(&mut self.z, value.z);
+    }
+
+    /// Level of the lowest bit, `None` if it is X or Z
+    pub fn to_bool(self) -> Option<bool> {
+        match self.bit(0) {
+            Logic::Zero => Some(false),
+            Logic::One => Some(true),
+            Logic::X | Logic::Z => None,
+        }
+    }
+
+    /// Passes the value through with Z turned into X, like the input of a buffer
+    pub fn buffer(self) -> Self {
+        Self::known(self.ones(), self.zeros())
+    }
+
+    /// Combines two drivers of the same wire, an undriven bit takes the level of the other driver
+    /// and drivers that disagree give X.
+    pub fn resolve(self, other: Self) -> Self {
+        let driven = !self.z & !other.z;
+        let ones =
+            self.z & other.ones() | other.z & self.ones() | driven & self.ones() & other.ones();
+        let zeros =
+            self.z & other.zeros() | other.z & self.zeros() | driven & self.zeros() & other.zeros();
+        let z = self.z & other.z;
+
+        Self {
+            bits: ones,
+            x: !(ones | zeros | z),
+            z,
+        }
+    }
+
+    /// Single bit values as one digit, buses as `0x` followed by hex digits
+    pub fn format(self, width: u8) -> String {
+        match width {
+            1 => self.to_hex(1),
+            _ => format!("0x{}", self.to_hex(width)),
+        }
+    }
+
+    /// Hex digits of the lowest `width` bits, a digit is X if any of its bits are unknown and Z if
+    /// none of them are driven
+    pub fn to_hex(self, width: u8) -> String {
+        (0..width.div_ceil(4))
+            .rev()
+            .map(|digit| {
+                let shift = digit * 4;
+                let mask = crate::wire::mask((width - shift).min(4)) << shift;
+
+                if self.z & mask == mask {
+                    'Z'
+                } else if (self.x | self.z) & mask != 0 {
+                    'X'
+                } else {
+                    char::from_digit(((self.bits & mask) >> shift) as u32, 16)
+                        .unwrap()
+                        .to_ascii_uppercase()
+                }
+            })
+            .collect()
+    }
+}
@@ -1,8 +1,9 @@
+use std::path::Path;
+
 use raylib::prelude::*;
 
 mod app;
-mod renderer;
-mod wire;
+mod history;
 
 use app::App;
 
@@ -20,8 +21,15 @@ fn main() {
 
     let mut app = App::new();
 
+    if let Some(path) = std::env::args().nth(1)
+        && let Err(err) = app.open(Path::new(&path))
+    {
+        eprintln!("failed to open {path}: {err}");
+    }
+
     while !rl.window_should_close() {
         app.handle_events(&mut rl);
+        app.advance(rl.get_frame_time());
 
         let mut d = rl.begin_drawing(&thread);
         d.clear_background(Color::BLACK);
@@ -31,12 +39,3 @@ fn main() {
         app.draw(&mut d);
     }
 }
-
-pub fn id_salt() -> usize {
-    static mut COUNTER: usize = 0;
-
-    unsafe {
-        COUNTER += 1;
-        COUNTER - 1
-    }
-}
@@ -0,0 +1,373 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, HashSet};
+
+use crate::expr::Expr;
+
+/// Formulas are only simplified up to this many variables
+pub const MAX_VARIABLES: usize = 12;
+
+/// Product term, bits set in `mask` are don't care and always clear in `bits`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Implicant {
+    pub bits: u32,
+    pub mask: u32,
+}
+
+impl Implicant {
+    pub fn covers(self, minterm: u32) -> bool {
+        minterm & !self.mask == self.bits
+    }
+
+    /// Variables the term depends on, with their required level
+    fn literals(self, vars: &[String]) -> impl Iterator<Item = (&String, bool)> {
+        let bit = move |idx: usize| 1 << (vars.len() - 1 - idx);
+
+        vars.iter()
+            .enumerate()
+            .filter(move |&(idx, _)| self.mask & bit(idx) == 0)
+            .map(move |(idx, var)| (var, self.bits & bit(idx) != 0))
+    }
+
+    /// Dash notation over `vars` variables, `1-0` for `a & !c`
+    pub fn pattern(self, vars: usize) -> String {
+        (0..vars)
+            .rev()
+            .map(|bit| match (self.mask >> bit & 1, self.bits >> bit & 1) {
+                (1, _) => '-',
+                (_, 1) => '1',
+                _ => '0',
+            })
+            .collect()
+    }
+
+    /// Number of variables the term doesn't depend on, each one halves the literals needed
+    fn size(self) -> u32 {
+        self.mask.count_ones()
+    }
+
+    /// The term as a product, `a & !b`
+    pub fn product(self, vars: &[String]) -> Expr {
+        self.literals(vars)
+            .map(|(var, level)| literal(var, level))
+            .reduce(|a, b| a & b)
+            .unwrap_or(Expr::Const(true))
+    }
+
+    /// The term over maxterms as a sum, `!a | b`
+    pub fn sum(self, vars: &[String]) -> Expr {
+        self.literals(vars)
+            .map(|(var, level)| literal(var, !level))
+            .reduce(|a, b| a | b)
+            .unwrap_or(Expr::Const(false))
+    }
+}
+
+fn literal(var: &str, level: bool) -> Expr {
+    match level {
+        true => Expr::Var(var.to_string()),
+        false => !Expr::Var(var.to_string()),
+    }
+}
+
+/// Quine–McCluskey, combines terms differing in a single variable until nothing combines
+/// anymore, the terms left over are the prime implicants. `terms` are the minterms together with
+/// the don't cares.
+pub fn prime_implicants(terms: &[u32], vars: usize) -> Vec<Implicant> {
+    let mut terms = terms
+        .iter()
+        .map(|&bits| Implicant { bits, mask: 0 })
+        .collect::<BTreeSet<_>>();
+    let mut primes = vec![];
+
+    while !terms.is_empty() {
+        let mut next = BTreeSet::new();
+        let mut combined = HashSet::new();
+
+        for &term in &terms {
+            for bit in (0..vars).map(|idx| 1 << idx) {
+                if (term.bits | term.mask) & bit != 0 {
+                    continue;
+                }
+
+                let other = Implicant {
+                    bits: term.bits | bit,
+                    mask: term.mask,
+                };
+
+                if terms.contains(&other) {
+                    next.insert(Implicant {
+                        bits: term.bits,
+                        mask: term.mask | bit,
+                    });
+                    combined.insert(term);
+                    combined.insert(other);
+                }
+            }
+        }
+
+        primes.extend(terms.iter().filter(|term| !combined.contains(term)));
+        terms = next;
+    }
+
+    primes
+}
+
+/// Prime implicants that are the only ones covering one of the minterms
+pub fn essential(primes: &[Implicant], minterms: &[u32]) -> Vec<Implicant> {
+    let mut essential = vec![];
+
+    for &minterm in minterms {
+        let mut covering = primes.iter().filter(|prime| prime.covers(minterm));
+        if let (Some(&prime), None) = (covering.next(), covering.next())
+            && !essential.contains(&prime)
+        {
+            essential.push(prime);
+        }
+    }
+
+    essential
+}
+
+/// Picks prime implicants covering every minterm, the essential ones and then a minimal choice
+/// for the rest found with Petrick's method. Falls back to taking whichever covers the most
+/// minterms that are left if that blows up.
+pub fn cover(primes: &[Implicant], minterms: &[u32]) -> Vec<Implicant> {
+    let mut chosen = essential(primes, minterms);
+
+    let left = minterms
+        .iter()
+        .copied()
+        .filter(|&minterm| !chosen.iter().any(|prime| prime.covers(minterm)))
+        .collect::<Vec<_>>();
+
+    match petrick(primes, &left) {
+        Some(rest) => chosen.extend(rest),
+        None => chosen.extend(greedy(primes, left)),
+    }
+
+    chosen.sort();
+    chosen
+}
+
+/// Products kept while expanding in Petrick's method before giving up
+const MAX_PRODUCTS: usize = 1 << 12;
+
+/// Multiplies out the product of "one of the implicants covering this minterm" sums, every
+/// product of the result is a cover and the smallest is the minimal one.
+fn petrick(primes: &[Implicant], minterms: &[u32]) -> Option<Vec<Implicant>> {
+    let candidates = primes
+        .iter()
+        .copied()
+        .filter(|prime| minterms.iter().any(|&minterm| prime.covers(minterm)))
+        .collect::<Vec<_>>();
+
+    // products are sets of candidates
+    if candidates.len() > u128::BITS as usize {
+        return None;
+    }
+
+    let mut products = vec![0u128];
+    for &minterm in minterms {
+        let sum = (0..candidates.len())
+            .filter(|&idx| candidates[idx].covers(minterm))
+            .map(|idx| 1u128 << idx)
+            .collect::<Vec<_>>();
+
+        let mut next = vec![];
+        for product in products {
+            match sum.iter().any(|&term| product & term != 0) {
+                true => next.push(product),
+                false => next.extend(sum.iter().map(|&term| product | term)),
+            }
+        }
+
+        // absorption, x + xy = x
+        next.sort_by_key(|product| (product.count_ones(), *product));
+        next.dedup();
+        products = vec![];
+        for product in next {
+            if products.iter().all(|&other| product & other != other) {
+                products.push(product);
+            }
+        }
+
+        if products.len() > MAX_PRODUCTS {
+            return None;
+        }
+    }
+
+    let terms = |product: u128| (0..candidates.len()).filter(move |idx| product >> idx & 1 == 1);
+
+    // fewest terms, then fewest literals
+    let best = products.into_iter().min_by_key(|&product| {
+        let size = terms(product)
+            .map(|idx| candidates[idx].size())
+            .sum::<u32>();
+        (product.count_ones(), Reverse(size))
+    })?;
+
+    Some(terms(best).map(|idx| candidates[idx]).collect())
+}
+
+fn greedy(primes: &[Implicant], mut left: Vec<u32>) -> Vec<Implicant> {
+    let mut chosen = vec![];
+
+    while !left.is_empty() {
+        // ties go to the term with fewer literals
+        let &best = primes
+            .iter()
+            .max_by_key(|prime| {
+                let covered = left
+                    .iter()
+                    .filter(|&&minterm| prime.covers(minterm))
+                    .count();
+                (covered, prime.size())
+            })
+            .unwrap();
+
+        left.retain(|&minterm| !best.covers(minterm));
+        chosen.push(best);
+    }
+
+    chosen
+}
+
+/// Prime implicants of `minterms` together with `dont_cares`, leaving out the ones covering
+/// nothing but don't cares as they are of no use
+pub fn primes(minterms: &[u32], dont_cares: &[u32], vars: usize) -> Vec<Implicant> {
+    let mut primes = prime_implicants(&[minterms, dont_cares].concat(), vars);
+    primes.retain(|prime| minterms.iter().any(|&minterm| prime.covers(minterm)));
+    primes
+}
+
+/// Minimal cover of `minterms`, rows in `dont_cares` can go either way. Rows are numbered with
+/// the first of the `vars` variables as the most significant bit.
+pub fn minimize(minterms: &[u32], dont_cares: &[u32], vars: usize) -> Vec<Implicant> {
+    cover(&primes(minterms, dont_cares, vars), minterms)
+}
+
+/// Rows that are neither minterms nor don't cares
+pub fn maxterms(minterms: &[u32], dont_cares: &[u32], vars: usize) -> Vec<u32> {
+    (0..1 << vars)
+        .filter(|row| !minterms.contains(row) && !dont_cares.contains(row))
+        .collect()
+}
+
+/// Sum of the implicants as products
+pub fn sum_of_products(terms: &[Implicant], vars: &[String]) -> Expr {
+    terms
+        .iter()
+        .map(|term| term.product(vars))
+        .reduce(|a, b| a | b)
+        .unwrap_or(Expr::Const(false))
+}
+
+/// Product of implicants of the complement as sums
+pub fn product_of_sums(terms: &[Implicant], vars: &[String]) -> Expr {
+    terms
+        .iter()
+        .map(|term| term.sum(vars))
+        .reduce(|a, b| a & b)
+        .unwrap_or(Expr::Const(true))
+}
+
+/// Minimal sum of products for the rows in `minterms`
+pub fn sop(vars: &[String], minterms: &[u32]) -> Expr {
+    sum_of_products(&minimize(minterms, &[], vars.len()), vars)
+}
+
+/// Minimal product of sums for the rows in `minterms`, found by minimizing the rows where the
+/// formula is false
+pub fn pos(vars: &[String], minterms: &[u32]) -> Expr {
+    let maxterms = maxterms(minterms, &[], vars.len());
+    product_of_sums(&minimize(&maxterms, &[], vars.len()), vars)
+}
+
+/// Karnaugh maps are drawn up to this many variables
+pub const MAX_MAP_VARIABLES: usize = 6;
+
+/// Gray code sequence of `bits` bits, neighbours differ in a single bit
+pub fn gray_code(bits: usize) -> Vec<u32> {
+    (0..1u32 << bits).map(|idx| idx ^ idx >> 1).collect()
+}
+
+/// Row numbers laid out as a Karnaugh map, the first half of the variables (rounded down) pick
+/// the row and the rest the column, both in Gray code order so adjacent cells differ in one
+/// variable.
+pub fn karnaugh_map(vars: usize) -> Vec<Vec<u32>> {
+    let columns = vars - vars / 2;
+
+    gray_code(vars / 2)
+        .into_iter()
+        .map(|row| {
+            gray_code(columns)
+                .into_iter()
+                .map(|column| row << columns | column)
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patterns(minterms: &[u32], dont_cares: &[u32], vars: usize) -> Vec<String> {
+        let mut patterns = minimize(minterms, dont_cares, vars)
+            .into_iter()
+            .map(|term| term.pattern(vars))
+            .collect::<Vec<_>>();
+        patterns.sort();
+        patterns
+    }
+
+    #[test]
+    fn four_variable_map() {
+        // !b!c | c!d | !abd, the prime !b!d isn't needed
+        assert_eq!(
+            patterns(&[0, 1, 2, 5, 6, 7, 8, 9, 10, 14], &[], 4),
+            ["--10", "-00-", "01-1"]
+        );
+    }
+
+    #[test]
+    fn dont_cares_grow_the_groups() {
+        // cd | !a!b, rows 0 and 2 only help
+        assert_eq!(
+            patterns(&[1, 3, 7, 11, 15], &[0, 2, 5], 4),
+            ["--11", "00--"]
+        );
+    }
+
+    #[test]
+    fn corners_of_the_map_are_adjacent() {
+        assert_eq!(patterns(&[0, 2, 8, 10], &[], 4), ["-0-0"]);
+    }
+
+    #[test]
+    fn constants() {
+        assert_eq!(patterns(&[0, 1, 2, 3], &[], 2), ["--"]);
+        assert!(patterns(&[], &[], 2).is_empty());
+    }
+
+    #[test]
+    fn products_and_sums() {
+        let vars = ["a", "b"].map(String::from);
+        assert_eq!(sop(&vars, &[1, 2]).to_string(), "!a & b | a & !b");
+        assert_eq!(pos(&vars, &[1, 2]).to_string(), "(a | b) & (!a | !b)");
+    }
+
+    #[test]
+    fn map_is_in_gray_code() {
+        assert_eq!(
+            karnaugh_map(4),
+            [[0, 1, 3, 2], [4, 5, 7, 6], [12, 13, 15, 14], [8, 9, 11, 10]]
+        );
+    }
+
+    #[test]
+    fn primes_of_nothing_but_dont_cares_are_left_out() {
+        // -11 only covers don't cares
+        assert_eq!(primes(&[0], &[3, 7], 3), [Implicant { bits: 0, mask: 0 }]);
+    }
+}
@@ -1,10 +0,0 @@
-use raylib::prelude::*;
-
-use crate::id_salt;
-use crate::PIN_RADIUS;
-use crate::renderer;
-use crate::wire::*;
-
-pub fn draw_node(d: &mut RaylibDrawHandle) {
-    
-}
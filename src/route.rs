@@ -0,0 +1,333 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+/// How a wire is drawn between its pins
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WireStyle {
+    #[default]
+    Curved,
+    /// Horizontal and vertical segments going around nodes
+    Orthogonal,
+}
+
+/// Style of a wire and the points it has to pass through, in canvas coordinates. Waypoints
+/// only apply to orthogonal wires.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Route {
+    pub style: WireStyle,
+    pub waypoints: Vec<Vec2>,
+}
+
+impl Route {
+    pub fn new(style: WireStyle) -> Self {
+        Self {
+            style,
+            waypoints: vec![],
+        }
+    }
+
+    /// Curved without waypoints, how wires are drawn when nothing is stored for them
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Index at which `point` joins the waypoints with the least detour, between `start` and
+    /// `end`
+    pub fn insertion_index(&self, start: Vec2, end: Vec2, point: Vec2) -> usize {
+        let mut points = vec![start];
+        points.extend(&self.waypoints);
+        points.push(end);
+
+        (0..points.len() - 1)
+            .min_by(|&a, &b| {
+                let detour = |idx: usize| {
+                    let (from, to) = (points[idx], points[idx + 1]);
+                    from.distance(point) + point.distance(to) - from.distance(to)
+                };
+                detour(a).total_cmp(&detour(b))
+            })
+            .unwrap_or(0)
+    }
+}
+
+/// Space kept between a wire and the rectangles it goes around
+pub const CLEARANCE: f32 = 10.0;
+/// Length a bend is worth, routes take a detour of up to this much to save one
+const BEND_COST: f32 = 40.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+// entry of the search queue, cheapest first
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct State {
+    cost: f32,
+    point: (usize, usize),
+    direction: Direction,
+}
+
+impl Eq for State {}
+
+impl Ord for State {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+impl PartialOrd for State {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Horizontal and vertical segments from `start` to `end` around `obstacles`, given by their
+/// top left and bottom right corners. Returns the corners of the route including both ends.
+///
+/// Searches the grid formed by the ends and the edges of the obstacles grown by `CLEARANCE`,
+/// leaving `start` and reaching `end` to the right and preferring few bends. Obstacles
+/// containing either end are ignored, if there is no way around the others the route cuts
+/// straight through.
+pub fn orthogonal(start: Vec2, end: Vec2, obstacles: &[(Vec2, Vec2)]) -> Vec<Vec2> {
+    let inside =
+        |point: Vec2, (min, max): (Vec2, Vec2)| point.cmpgt(min).all() && point.cmplt(max).all();
+
+    let obstacles = obstacles
+        .iter()
+        .map(|&(min, max)| (min - CLEARANCE, max + CLEARANCE))
+        .filter(|&rect| !inside(start, rect) && !inside(end, rect))
+        .collect::<Vec<_>>();
+
+    let coordinates = |axis: fn(Vec2) -> f32| {
+        let mut values = vec![axis(start), axis(end), (axis(start) + axis(end)) / 2.0];
+        values.extend(
+            obstacles
+                .iter()
+                .flat_map(|&(min, max)| [axis(min), axis(max)]),
+        );
+        values.sort_by(f32::total_cmp);
+        values.dedup();
+        values
+    };
+    let (xs, ys) = (coordinates(|v| v.x), coordinates(|v| v.y));
+
+    let at = |(i, j): (usize, usize)| Vec2::new(xs[i], ys[j]);
+    let blocked = |point: Vec2| obstacles.iter().any(|&rect| inside(point, rect));
+    let index = |values: &[f32], value: f32| values.iter().position(|&v| v == value).unwrap();
+
+    let from = (index(&xs, start.x), index(&ys, start.y));
+    let to = (index(&xs, end.x), index(&ys, end.y));
+
+    let mut best = HashMap::new();
+    let mut previous = HashMap::new();
+    let mut queue = BinaryHeap::new();
+
+    let first = State {
+        cost: 0.0,
+        point: from,
+        direction: Direction::Right,
+    };
+    best.insert((from, Direction::Right), 0.0);
+    queue.push(first);
+
+    // arriving from the left is worth a bend, the wire goes on into the pin to the right
+    let mut last: Option<(f32, State)> = None;
+    while let Some(state) = queue.pop() {
+        if last.is_some_and(|(cost, _)| state.cost > cost) {
+            break;
+        }
+        if best.get(&(state.point, state.direction)) != Some(&state.cost) {
+            continue;
+        }
+        if state.point == to {
+            let cost = match state.direction {
+                Direction::Right => state.cost,
+                _ => state.cost + BEND_COST,
+            };
+            // on a tie the wire going straight into the pin wins
+            if last.is_none_or(|(known, _)| {
+                cost < known || cost == known && state.direction == Direction::Right
+            }) {
+                last = Some((cost, state));
+            }
+            continue;
+        }
+
+        let (i, j) = state.point;
+        let neighbours = [
+            (Direction::Left, i.checked_sub(1).map(|i| (i, j))),
+            (Direction::Right, (i + 1 < xs.len()).then_some((i + 1, j))),
+            (Direction::Up, j.checked_sub(1).map(|j| (i, j))),
+            (Direction::Down, (j + 1 < ys.len()).then_some((i, j + 1))),
+        ];
+
+        for (direction, next) in neighbours {
+            let Some(next) = next else {
+                continue;
+            };
+
+            // every obstacle edge is on the grid, so a segment crossing one has its middle inside
+            let (a, b) = (at(state.point), at(next));
+            if blocked((a + b) / 2.0) || blocked(b) {
+                continue;
+            }
+
+            let mut cost = state.cost + a.distance(b);
+            if direction != state.direction {
+                cost += BEND_COST;
+            }
+
+            let key = (next, direction);
+            if best.get(&key).is_none_or(|&known| cost < known) {
+                best.insert(key, cost);
+                previous.insert(key, (state.point, state.direction));
+                queue.push(State {
+                    cost,
+                    point: next,
+                    direction,
+                });
+            }
+        }
+    }
+
+    let Some((_, last)) = last else {
+        let middle = (start.x + end.x) / 2.0;
+        return simplify(vec![
+            start,
+            Vec2::new(middle, start.y),
+            Vec2::new(middle, end.y),
+            end,
+        ]);
+    };
+
+    let mut points = vec![at(last.point)];
+    let mut key = (last.point, last.direction);
+    while let Some(&(point, direction)) = previous.get(&key) {
+        points.push(at(point));
+        key = (point, direction);
+    }
+    points.reverse();
+
+    simplify(points)
+}
+
+/// Drops repeated points and the ones in the middle of a straight segment
+pub fn simplify(points: Vec<Vec2>) -> Vec<Vec2> {
+    let mut simplified: Vec<Vec2> = Vec::with_capacity(points.len());
+
+    for point in points {
+        if simplified.last() == Some(&point) {
+            continue;
+        }
+
+        if let [.., a, b] = simplified[..]
+            && ((a.x == b.x && b.x == point.x) || (a.y == b.y && b.y == point.y))
+        {
+            simplified.pop();
+        }
+        simplified.push(point);
+    }
+
+    simplified
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn crosses((min, max): (Vec2, Vec2), a: Vec2, b: Vec2) -> bool {
+        a.max(b).cmpgt(min).all() && a.min(b).cmplt(max).all()
+    }
+
+    fn is_orthogonal(points: &[Vec2]) -> bool {
+        points
+            .windows(2)
+            .all(|w| w[0].x == w[1].x || w[0].y == w[1].y)
+    }
+
+    #[test]
+    fn straight_when_nothing_is_in_the_way() {
+        let (start, end) = (Vec2::new(0.0, 0.0), Vec2::new(200.0, 0.0));
+        assert_eq!(orthogonal(start, end, &[]), [start, end]);
+    }
+
+    #[test]
+    fn goes_around_a_blocking_node() {
+        let (start, end) = (Vec2::new(0.0, 0.0), Vec2::new(200.0, 0.0));
+        let node = (Vec2::new(80.0, -30.0), Vec2::new(120.0, 30.0));
+        let points = orthogonal(start, end, &[node]);
+
+        assert_eq!(points.first(), Some(&start));
+        assert_eq!(points.last(), Some(&end));
+        assert!(is_orthogonal(&points), "{points:?}");
+
+        let grown = (node.0 - CLEARANCE, node.1 + CLEARANCE);
+        for pair in points.windows(2) {
+            assert!(!crosses(grown, pair[0], pair[1]), "{points:?}");
+        }
+    }
+
+    #[test]
+    fn arrives_heading_right() {
+        let (start, end) = (Vec2::new(0.0, 0.0), Vec2::new(100.0, 80.0));
+        let points = orthogonal(start, end, &[]);
+
+        assert!(is_orthogonal(&points), "{points:?}");
+        let [.., a, b] = points[..] else {
+            panic!("{points:?}");
+        };
+        assert!(a.y == b.y && a.x < b.x, "{points:?}");
+    }
+
+    #[test]
+    fn nodes_around_the_ends_are_ignored() {
+        let (start, end) = (Vec2::new(0.0, 0.0), Vec2::new(200.0, 0.0));
+        let around_end = (Vec2::new(190.0, -20.0), Vec2::new(240.0, 20.0));
+        assert_eq!(orthogonal(start, end, &[around_end]), [start, end]);
+    }
+
+    #[test]
+    fn simplify_drops_repeated_and_collinear_points() {
+        assert_eq!(simplify(vec![]), []);
+        assert_eq!(simplify(vec![Vec2::ONE]), [Vec2::ONE]);
+        assert_eq!(simplify(vec![Vec2::ONE, Vec2::ONE]), [Vec2::ONE]);
+
+        let points = [
+            (0.0, 0.0),
+            (10.0, 0.0),
+            (10.0, 0.0),
+            (20.0, 0.0),
+            (20.0, 10.0),
+        ];
+        let points = points.map(|(x, y)| Vec2::new(x, y)).to_vec();
+        assert_eq!(
+            simplify(points),
+            [
+                Vec2::new(0.0, 0.0),
+                Vec2::new(20.0, 0.0),
+                Vec2::new(20.0, 10.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn simplify_folds_a_segment_going_back() {
+        // the middle point is still on the line through its neighbours
+        let points = vec![
+            Vec2::new(0.0, 0.0),
+            Vec2::new(30.0, 0.0),
+            Vec2::new(10.0, 0.0),
+        ];
+        assert_eq!(
+            simplify(points),
+            [Vec2::new(0.0, 0.0), Vec2::new(10.0, 0.0)]
+        );
+    }
+}
@@ -0,0 +1,393 @@
+/// Variable or its negation, variable `v` is `2v` and its negation `2v + 1`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Lit(u32);
+
+impl Lit {
+    pub fn var(self) -> usize {
+        (self.0 >> 1) as usize
+    }
+
+    pub fn is_negated(self) -> bool {
+        self.0 & 1 == 1
+    }
+
+    fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl std::ops::Not for Lit {
+    type Output = Lit;
+
+    fn not(self) -> Lit {
+        Lit(self.0 ^ 1)
+    }
+}
+
+/// Conflicts before the first restart, grows by half after every restart
+const RESTART_INTERVAL: usize = 100;
+
+/// Small CDCL solver: two watched literals, first UIP clause learning and activity based
+/// decisions with phase saving.
+#[derive(Debug, Clone, Default)]
+pub struct Solver {
+    clauses: Vec<Vec<Lit>>,
+    // clauses watching each literal, visited when it becomes false
+    watches: Vec<Vec<usize>>,
+    units: Vec<Lit>,
+    // an empty clause was added
+    unsatisfiable: bool,
+
+    values: Vec<Option<bool>>,
+    levels: Vec<usize>,
+    // clause that implied each variable, `None` for decisions
+    reasons: Vec<Option<usize>>,
+    phases: Vec<bool>,
+    activity: Vec<f64>,
+    increment: f64,
+
+    trail: Vec<Lit>,
+    // trail length at the start of every decision level
+    trail_lim: Vec<usize>,
+    // next trail entry to propagate
+    head: usize,
+}
+
+impl Solver {
+    pub fn new() -> Self {
+        Self {
+            increment: 1.0,
+            ..Default::default()
+        }
+    }
+
+    /// Adds a variable, returned as its positive literal
+    pub fn new_var(&mut self) -> Lit {
+        let var = self.values.len();
+        self.values.push(None);
+        self.levels.push(0);
+        self.reasons.push(None);
+        self.phases.push(false);
+        self.activity.push(0.0);
+        self.watches.extend([vec![], vec![]]);
+
+        Lit(var as u32 * 2)
+    }
+
+    pub fn add_clause(&mut self, clause: &[Lit]) {
+        let mut clause = clause.to_vec();
+        clause.sort_by_key(|lit| lit.0);
+        clause.dedup();
+
+        // always true
+        if clause.windows(2).any(|pair| pair[0] == !pair[1]) {
+            return;
+        }
+
+        match clause.len() {
+            0 => self.unsatisfiable = true,
+            1 => self.units.push(clause[0]),
+            _ => {
+                self.attach(clause);
+            }
+        }
+    }
+
+    fn attach(&mut self, clause: Vec<Lit>) -> usize {
+        let idx = self.clauses.len();
+        self.watches[clause[0].index()].push(idx);
+        self.watches[clause[1].index()].push(idx);
+        self.clauses.push(clause);
+        idx
+    }
+
+    fn value(&self, lit: Lit) -> Option<bool> {
+        self.values[lit.var()].map(|value| value != lit.is_negated())
+    }
+
+    fn level(&self) -> usize {
+        self.trail_lim.len()
+    }
+
+    fn assign(&mut self, lit: Lit, reason: Option<usize>) {
+        let var = lit.var();
+        self.values[var] = Some(!lit.is_negated());
+        self.levels[var] = self.level();
+        self.reasons[var] = reason;
+        self.trail.push(lit);
+    }
+
+    /// Assignment satisfying every clause, indexed by variable, or `None` if there is none
+    pub fn solve(&mut self) -> Option<Vec<bool>> {
+        if self.unsatisfiable {
+            return None;
+        }
+
+        for lit in self.units.clone() {
+            match self.value(lit) {
+                Some(true) => {}
+                Some(false) => return None,
+                None => self.assign(lit, None),
+            }
+        }
+
+        let mut conflicts = 0;
+        let mut restart = RESTART_INTERVAL;
+
+        loop {
+            if let Some(conflict) = self.propagate() {
+                if self.level() == 0 {
+                    return None;
+                }
+
+                let (learnt, level) = self.analyze(conflict);
+                self.backtrack(level);
+
+                match learnt.len() {
+                    1 => self.assign(learnt[0], None),
+                    _ => {
+                        let lit = learnt[0];
+                        let reason = self.attach(learnt);
+                        self.assign(lit, Some(reason));
+                    }
+                }
+
+                self.increment /= 0.95;
+                conflicts += 1;
+                continue;
+            }
+
+            if conflicts >= restart {
+                conflicts = 0;
+                restart += restart / 2;
+                self.backtrack(0);
+            }
+
+            // most active unassigned variable, in its last phase
+            let var = (0..self.values.len())
+                .filter(|&var| self.values[var].is_none())
+                .max_by(|&a, &b| self.activity[a].total_cmp(&self.activity[b]));
+
+            let Some(var) = var else {
+                return Some(self.values.iter().map(|value| value.unwrap()).collect());
+            };
+
+            self.trail_lim.push(self.trail.len());
+            let lit = Lit(var as u32 * 2);
+            self.assign(if self.phases[var] { lit } else { !lit }, None);
+        }
+    }
+
+    /// Unit propagation over the trail, returns a clause with every literal false if it runs
+    /// into one
+    fn propagate(&mut self) -> Option<usize> {
+        while self.head < self.trail.len() {
+            let false_lit = !self.trail[self.head];
+            self.head += 1;
+
+            let mut watchers = std::mem::take(&mut self.watches[false_lit.index()]);
+            let mut idx = 0;
+
+            while idx < watchers.len() {
+                let clause = watchers[idx];
+
+                // keep the false literal in the second slot
+                if self.clauses[clause][0] == false_lit {
+                    self.clauses[clause].swap(0, 1);
+                }
+
+                let first = self.clauses[clause][0];
+                if self.value(first) == Some(true) {
+                    idx += 1;
+                    continue;
+                }
+
+                let other = (2..self.clauses[clause].len())
+                    .find(|&k| self.value(self.clauses[clause][k]) != Some(false));
+
+                if let Some(k) = other {
+                    self.clauses[clause].swap(1, k);
+                    let lit = self.clauses[clause][1];
+                    self.watches[lit.index()].push(clause);
+                    watchers.swap_remove(idx);
+                    continue;
+                }
+
+                if self.value(first) == Some(false) {
+                    self.watches[false_lit.index()] = watchers;
+                    return Some(clause);
+                }
+
+                self.assign(first, Some(clause));
+                idx += 1;
+            }
+
+            self.watches[false_lit.index()] = watchers;
+        }
+
+        None
+    }
+
+    /// Learns the first UIP clause of a conflict, asserting literal first, and the level to
+    /// jump back to
+    fn analyze(&mut self, conflict: usize) -> (Vec<Lit>, usize) {
+        let mut seen = vec![false; self.values.len()];
+        let mut learnt = vec![Lit(0)];
+        let mut pending = 0;
+        let mut clause = conflict;
+        let mut implied = None;
+        let mut idx = self.trail.len();
+
+        loop {
+            for &lit in &self.clauses[clause] {
+                let var = lit.var();
+                if Some(lit) == implied || seen[var] || self.levels[var] == 0 {
+                    continue;
+                }
+
+                seen[var] = true;
+                self.activity[var] += self.increment;
+                match self.levels[var] == self.level() {
+                    true => pending += 1,
+                    false => learnt.push(lit),
+                }
+            }
+
+            // latest literal of the current level involved in the conflict
+            loop {
+                idx -= 1;
+                if seen[self.trail[idx].var()] {
+                    break;
+                }
+            }
+
+            let lit = self.trail[idx];
+            pending -= 1;
+            if pending == 0 {
+                learnt[0] = !lit;
+                break;
+            }
+
+            clause = self.reasons[lit.var()].unwrap();
+            implied = Some(lit);
+        }
+
+        if self.activity.iter().any(|&activity| activity > 1e100) {
+            self.activity
+                .iter_mut()
+                .for_each(|activity| *activity *= 1e-100);
+            self.increment *= 1e-100;
+        }
+
+        // the second literal gets watched, it has to be the last one to be unassigned
+        let mut level = 0;
+        for idx in 1..learnt.len() {
+            if self.levels[learnt[idx].var()] > level {
+                level = self.levels[learnt[idx].var()];
+                learnt.swap(1, idx);
+            }
+        }
+
+        (learnt, level)
+    }
+
+    fn backtrack(&mut self, level: usize) {
+        if self.level() <= level {
+            return;
+        }
+
+        let start = self.trail_lim[level];
+        for lit in self.trail.drain(start..) {
+            self.values[lit.var()] = None;
+            self.phases[lit.var()] = !lit.is_negated();
+        }
+
+        self.trail_lim.truncate(level);
+        self.head = start;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // xorshift, enough to spread the clauses around
+    fn random(state: &mut u64) -> u64 {
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        *state
+    }
+
+    fn satisfies(clauses: &[Vec<Lit>], model: &[bool]) -> bool {
+        clauses.iter().all(|clause| {
+            clause
+                .iter()
+                .any(|&lit| model[lit.var()] != lit.is_negated())
+        })
+    }
+
+    fn brute_force(clauses: &[Vec<Lit>], vars: usize) -> bool {
+        (0..1u32 << vars).any(|row| {
+            let model = (0..vars).map(|var| row >> var & 1 == 1).collect::<Vec<_>>();
+            satisfies(clauses, &model)
+        })
+    }
+
+    #[test]
+    fn agrees_with_brute_force() {
+        let mut state = 0x2545_f491_4f6c_dd1d;
+
+        // up to five clauses per variable, past where random 3-SAT turns unsatisfiable
+        for vars in 1..=8 {
+            for _ in 0..200 {
+                let mut solver = Solver::new();
+                let lits = (0..vars).map(|_| solver.new_var()).collect::<Vec<_>>();
+
+                let count = random(&mut state) as usize % (vars * 5 + 1);
+                let clauses = (0..count)
+                    .map(|_| {
+                        let len = 1 + random(&mut state) as usize % 3;
+                        (0..len)
+                            .map(|_| {
+                                let lit = lits[random(&mut state) as usize % vars];
+                                if random(&mut state) & 1 == 1 {
+                                    !lit
+                                } else {
+                                    lit
+                                }
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                    .collect::<Vec<_>>();
+
+                for clause in &clauses {
+                    solver.add_clause(clause);
+                }
+
+                match solver.solve() {
+                    Some(model) => assert!(satisfies(&clauses, &model), "{clauses:?}"),
+                    None => assert!(!brute_force(&clauses, vars), "{clauses:?}"),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn empty_clause_is_unsatisfiable() {
+        let mut solver = Solver::new();
+        let a = solver.new_var();
+        solver.add_clause(&[a]);
+        solver.add_clause(&[]);
+        assert_eq!(solver.solve(), None);
+    }
+
+    #[test]
+    fn contradicting_units() {
+        let mut solver = Solver::new();
+        let a = solver.new_var();
+        solver.add_clause(&[a]);
+        solver.add_clause(&[!a]);
+        assert_eq!(solver.solve(), None);
+    }
+}
@@ -0,0 +1,65 @@
+use std::cell::Cell;
+
+/// Upper bound on the ticks simulated in a single frame so a high tick rate can't stall the
+/// window
+const MAX_TICKS_PER_FRAME: u32 = 1000;
+
+/// Simulation clock driving `Clock` nodes, ticks advance independently of the frame rate.
+#[derive(Debug)]
+pub struct Simulation {
+    pub running: Cell<bool>,
+    /// Ticks per second while running
+    pub tick_rate: Cell<f32>,
+    pub tick: Cell<u64>,
+
+    // single step requested from the ui
+    step: Cell<bool>,
+    accumulator: f32,
+}
+
+impl Default for Simulation {
+    fn default() -> Self {
+        Self {
+            running: false.into(),
+            tick_rate: 4.0.into(),
+            tick: 0.into(),
+            step: false.into(),
+            accumulator: 0.0,
+        }
+    }
+}
+
+impl Simulation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Advance by a single tick on the next frame, works while paused
+    pub fn step(&self) {
+        self.step.set(true);
+    }
+
+    /// Returns the number of ticks to simulate for a frame that took `dt` seconds
+    pub fn advance(&mut self, dt: f32) -> u32 {
+        let mut ticks = 0;
+
+        if self.running.get() {
+            self.accumulator += dt * self.tick_rate.get();
+            ticks = self.accumulator as u32;
+            self.accumulator -= ticks as f32;
+
+            if ticks > MAX_TICKS_PER_FRAME {
+                ticks = MAX_TICKS_PER_FRAME;
+                self.accumulator = 0.0;
+            }
+        } else {
+            self.accumulator = 0.0;
+        }
+
+        if self.step.replace(false) {
+            ticks += 1;
+        }
+
+        ticks
+    }
+}
@@ -0,0 +1,278 @@
+use std::collections::{HashMap, HashSet};
+
+use glam::Vec2;
+
+use crate::circuit::Circuit;
+use crate::expr::Expr;
+use crate::logic::Value;
+use crate::synth;
+use crate::truth_table::TruthTable;
+use crate::wire::*;
+
+/// Gates picked out of a circuit to be analyzed or replaced on their own
+#[derive(Debug, Clone)]
+pub struct Subnetwork {
+    pub nodes: HashSet<NodeId>,
+    /// Pins outside the sub-network it reads from, with a name for each
+    pub inputs: Vec<(SocketRef, String)>,
+    /// Outputs read outside the sub-network, with a name and the pins reading them
+    pub outputs: Vec<(SocketRef, String, Vec<SocketRef>)>,
+}
+
+impl Subnetwork {
+    /// The gates among `selected`, or every gate if nothing is selected. Inputs and displays are
+    /// left outside so they become the inputs and outputs of the sub-network.
+    pub fn new(nodes: &Circuit, selected: &HashSet<NodeId>) -> Result<Self, String> {
+        let mut inside = HashSet::new();
+
+        for node in nodes {
+            if !selected.is_empty() && !selected.contains(&node.id())
+                || matches!(node.kind, NodeKind::Input(_) | NodeKind::Display(_))
+            {
+                continue;
+            }
+
+            let label = node.label(nodes);
+            if !matches!(
+                node.kind,
+                NodeKind::NAnd
+                    | NodeKind::And
+                    | NodeKind::Or
+                    | NodeKind::XOr
+                    | NodeKind::Not
+                    | NodeKind::Constant(_)
+            ) {
+                return Err(format!("{label} is not a logic gate"));
+            }
+
+            if node.width > 1 {
+                return Err(format!("{label} is a bus"));
+            }
+
+            inside.insert(node.id());
+        }
+
+        if inside.is_empty() {
+            return Err("there are no gates to work on".to_string());
+        }
+
+        let pin_name = |socket: SocketRef| {
+            let node = nodes.get(socket.node_id).unwrap();
+            let pins = node.inputs.iter().chain(&node.outputs);
+            let pin = pins
+                .map(|pin| pin.borrow())
+                .find(|pin| pin.id == socket.socket_id)
+                .unwrap();
+
+            match node.kind {
+                NodeKind::Input(_) | NodeKind::Display(_) => node.label(nodes),
+                _ if node.outputs.len() == 1 && pin.kind == SocketKind::Output => node.label(nodes),
+                _ => format!("{}.{}", node.label(nodes), pin.name),
+            }
+        };
+
+        // sorted so the order doesn't depend on the hash map
+        let mut edges = nodes
+            .dependency_graph()
+            .iter()
+            .flat_map(|(&to, drivers)| drivers.iter().map(move |&from| Edge { from, to }))
+            .collect::<Vec<_>>();
+        edges.sort_by_key(|edge| (edge.from.node_id, edge.from.socket_id, edge.to.node_id));
+
+        let mut inputs: Vec<(SocketRef, String)> = vec![];
+        let mut outputs: Vec<(SocketRef, String, Vec<SocketRef>)> = vec![];
+
+        for Edge { from, to } in edges {
+            match (inside.contains(&from.node_id), inside.contains(&to.node_id)) {
+                (false, true) if !inputs.iter().any(|(input, _)| *input == from) => {
+                    inputs.push((from, pin_name(from)));
+                }
+
+                (true, false) => match outputs.iter_mut().find(|(output, ..)| *output == from) {
+                    Some((.., readers)) => readers.push(to),
+                    None => outputs.push((from, pin_name(from), vec![to])),
+                },
+
+                _ => {}
+            }
+        }
+
+        // a display reading an output names it better than the gate driving it does
+        for (_, name, readers) in &mut outputs {
+            if let Some(display) = readers.iter().find(|reader| {
+                nodes.iter().any(|node| {
+                    node.id() == reader.node_id && matches!(node.kind, NodeKind::Display(_))
+                })
+            }) {
+                *name = pin_name(*display);
+            }
+        }
+
+        if outputs.is_empty() {
+            return Err("nothing outside reads the gates".to_string());
+        }
+
+        Ok(Self {
+            nodes: inside,
+            inputs,
+            outputs,
+        })
+    }
+
+    /// Outputs for every combination of the inputs, evaluated on a copy of the gates with Input
+    /// and Display nodes attached in place of the surrounding circuit
+    pub fn truth_table(&self, nodes: &Circuit) -> TruthTable {
+        // fresh ids for the attached nodes
+        let mut ids = IdAllocator::after(nodes);
+
+        let mut circuit = Circuit::new();
+        let mut edges = vec![];
+
+        for (driver, name) in &self.inputs {
+            let mut input = NodeKind::Input(Value::new(0).into()).build(Vec2::ZERO, &mut ids);
+            input.name = name.clone();
+            let from = SocketRef {
+                node_id: input.id(),
+                socket_id: input.outputs[0].borrow().id,
+            };
+
+            for &to in nodes.fanout(*driver) {
+                if self.nodes.contains(&to.node_id) {
+                    edges.push(Edge { from, to });
+                }
+            }
+
+            circuit.push(input);
+        }
+
+        circuit.extend(
+            nodes
+                .iter()
+                .filter(|node| self.nodes.contains(&node.id()))
+                .cloned(),
+        );
+
+        for (&to, drivers) in nodes.dependency_graph() {
+            for &from in drivers {
+                if self.nodes.contains(&from.node_id) && self.nodes.contains(&to.node_id) {
+                    edges.push(Edge { from, to });
+                }
+            }
+        }
+
+        for (from, name, _) in &self.outputs {
+            let mut display = NodeKind::Display(Value::Z.into()).build(Vec2::ZERO, &mut ids);
+            display.name = name.clone();
+            edges.push(Edge {
+                from: *from,
+                to: SocketRef {
+                    node_id: display.id(),
+                    socket_id: display.inputs[0].borrow().id,
+                },
+            });
+
+            circuit.push(display);
+        }
+
+        circuit.rewire(edges);
+        TruthTable::new(&circuit, circuit.dependency_graph(), 0)
+    }
+
+    /// Names to use for the inputs in the formulas passed to `replacement`
+    pub fn variables(&self) -> Vec<String> {
+        // can't be confused with the name of a constant
+        (0..self.inputs.len())
+            .map(|idx| format!("#{idx}"))
+            .collect()
+    }
+
+    /// Gates computing `outputs`, formulas over `variables()` in the order of `self.outputs`,
+    /// wired to the pins around the sub-network. The sub-network itself and the wires touching
+    /// it are left for the caller to remove.
+    pub fn replacement(
+        &self,
+        outputs: &[Expr],
+        nand_only: bool,
+        origin: Vec2,
+        ids: &mut IdAllocator,
+    ) -> (Vec<Node>, Vec<Edge>) {
+        let equations = outputs
+            .iter()
+            .enumerate()
+            .map(|(idx, expr)| (format!("#{idx}"), expr.clone()))
+            .collect::<Vec<_>>();
+
+        let (mut nodes, edges) = synth::synthesize(&equations, nand_only, origin, ids);
+
+        // the synthesized inputs and displays stand in for the pins around the sub-network
+        let mut drivers = HashMap::new();
+        let mut readers = HashMap::new();
+        for node in &nodes {
+            let Some(idx) = node
+                .name
+                .strip_prefix('#')
+                .and_then(|idx| idx.parse::<usize>().ok())
+            else {
+                continue;
+            };
+
+            match node.kind {
+                NodeKind::Input(_) => {
+                    let from = SocketRef {
+                        node_id: node.id(),
+                        socket_id: node.outputs[0].borrow().id,
+                    };
+                    drivers.insert(from, self.inputs[idx].0);
+                }
+                NodeKind::Display(_) => {
+                    let to = SocketRef {
+                        node_id: node.id(),
+                        socket_id: node.inputs[0].borrow().id,
+                    };
+                    readers.insert(to, &self.outputs[idx].2);
+                }
+                _ => {}
+            }
+        }
+
+        let edges = edges
+            .into_iter()
+            .flat_map(|Edge { from, to }| {
+                let from = drivers.get(&from).copied().unwrap_or(from);
+                let to = readers
+                    .get(&to)
+                    .map_or(vec![to], |readers| readers.to_vec());
+                to.into_iter().map(move |to| Edge { from, to })
+            })
+            .collect();
+
+        nodes.retain(|node| !node.name.starts_with('#'));
+        (nodes, edges)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn constant_outputs_are_driven_by_constants() {
+        let equations = crate::expr::parse_equations("y = a & b").unwrap();
+        let mut ids = IdAllocator::new();
+        let (nodes, edges) = synth::synthesize(&equations, false, Vec2::ZERO, &mut ids);
+        let mut circuit = Circuit::from(nodes);
+        circuit.rewire(edges);
+
+        let subnetwork = Subnetwork::new(&circuit, &HashSet::new()).unwrap();
+        let (nodes, edges) =
+            subnetwork.replacement(&[Expr::Const(true)], false, Vec2::ZERO, &mut ids);
+
+        let [constant] = &nodes[..] else {
+            panic!("{nodes:?}");
+        };
+        assert!(matches!(constant.kind, NodeKind::Constant(value) if value == Value::new(1)));
+        assert_eq!(edges.len(), 1);
+        assert_eq!(edges[0].from.node_id, constant.id());
+        assert_eq!(edges[0].to, subnetwork.outputs[0].2[0]);
+    }
+}
@@ -0,0 +1,215 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+
+use glam::Vec2;
+
+use crate::expr::Expr;
+use crate::logic::Value;
+use crate::wire::*;
+
+/// Distance between the columns of a synthesized circuit
+const COLUMN: f32 = 160.0;
+/// Distance between nodes in a column
+const ROW: f32 = 90.0;
+
+/// Builds a gate network computing every `(display name, formula)` pair.
+///
+/// Inputs are named after the variables and shared between the outputs, as are identical gates.
+/// Nodes are laid out left to right by their distance from the inputs, starting at `origin`.
+pub fn synthesize(
+    equations: &[(String, Expr)],
+    nand_only: bool,
+    origin: Vec2,
+    ids: &mut IdAllocator,
+) -> (Vec<Node>, Vec<Edge>) {
+    let mut builder = Builder {
+        nand_only,
+        ids,
+        nodes: vec![],
+        edges: vec![],
+        depth: HashMap::new(),
+        inputs: HashMap::new(),
+        constants: HashMap::new(),
+        gates: HashMap::new(),
+        inverted: HashMap::new(),
+    };
+
+    let outputs = equations
+        .iter()
+        .map(|(name, expr)| (name, builder.expr(expr)))
+        .collect::<Vec<_>>();
+
+    // displays line up in a column of their own
+    let last = builder.depth.values().copied().max().unwrap_or(0) + 1;
+    for (name, driver) in outputs {
+        let display = builder.node(NodeKind::Display(Value::Z.into()), &[driver]);
+        builder.nodes[display].name = name.clone();
+        builder.depth.insert(display, last);
+    }
+
+    let mut rows = HashMap::new();
+    for (idx, node) in builder.nodes.iter().enumerate() {
+        let column = builder.depth[&idx];
+        let row = rows.entry(column).or_insert(0);
+
+        *node.position.borrow_mut() = origin + Vec2::new(column as f32 * COLUMN, *row as f32 * ROW);
+        *row += 1;
+    }
+
+    (builder.nodes, builder.edges)
+}
+
+/// Nodes are referred to by their index in `nodes` while building
+struct Builder<'a> {
+    nand_only: bool,
+    ids: &'a mut IdAllocator,
+    nodes: Vec<Node>,
+    edges: Vec<Edge>,
+    /// Column of every node
+    depth: HashMap<usize, usize>,
+    inputs: HashMap<String, usize>,
+    constants: HashMap<bool, usize>,
+    // (kind, operands) of the gates built so far
+    gates: HashMap<(String, Vec<usize>), usize>,
+    // NAND gates used as inverters, so inverting twice gives back the operand
+    inverted: HashMap<usize, usize>,
+}
+
+impl Builder<'_> {
+    /// Adds a node with its first inputs driven by the operands
+    fn node(&mut self, kind: NodeKind, operands: &[usize]) -> usize {
+        let node = kind.build(Vec2::ZERO, self.ids);
+
+        for (input, &operand) in node.inputs.iter().zip(operands) {
+            let from = &self.nodes[operand];
+            self.edges.push(Edge {
+                from: SocketRef {
+                    node_id: from.id(),
+                    socket_id: from.outputs[0].borrow().id,
+                },
+                to: SocketRef {
+                    node_id: node.id(),
+                    socket_id: input.borrow().id,
+                },
+            });
+        }
+
+        let depth = operands.iter().map(|operand| self.depth[operand] + 1).max();
+        self.depth.insert(self.nodes.len(), depth.unwrap_or(0));
+        self.nodes.push(node);
+        self.nodes.len() - 1
+    }
+
+    fn input(&mut self, name: &str) -> usize {
+        if let Some(&input) = self.inputs.get(name) {
+            return input;
+        }
+
+        let input = self.node(NodeKind::Input(Cell::new(Value::new(0))), &[]);
+        self.nodes[input].name = name.to_string();
+        self.inputs.insert(name.to_string(), input);
+        input
+    }
+
+    fn constant(&mut self, value: bool) -> usize {
+        if let Some(&constant) = self.constants.get(&value) {
+            return constant;
+        }
+
+        let constant = self.node(NodeKind::Constant(Value::new(value as u64)), &[]);
+        self.nodes[constant].name = (value as u8).to_string();
+        self.constants.insert(value, constant);
+        constant
+    }
+
+    /// Reuses an identical gate if there is one, all of the gates used are commutative
+    fn gate(&mut self, kind: NodeKind, mut operands: Vec<usize>) -> usize {
+        operands.sort();
+        let key = (kind.to_string(), operands);
+
+        if let Some(&gate) = self.gates.get(&key) {
+            return gate;
+        }
+
+        let gate = self.node(kind, &key.1);
+        self.gates.insert(key, gate);
+        gate
+    }
+
+    fn nand(&mut self, a: usize, b: usize) -> usize {
+        self.gate(NodeKind::NAnd, vec![a, b])
+    }
+
+    fn invert(&mut self, a: usize) -> usize {
+        if !self.nand_only {
+            return self.gate(NodeKind::Not, vec![a]);
+        }
+
+        if let Some(&operand) = self.inverted.get(&a) {
+            return operand;
+        }
+
+        let gate = self.nand(a, a);
+        self.inverted.insert(gate, a);
+        gate
+    }
+
+    fn expr(&mut self, expr: &Expr) -> usize {
+        match expr {
+            Expr::Const(value) => self.constant(*value),
+            Expr::Var(name) => self.input(name),
+            Expr::Not(a) => {
+                let a = self.expr(a);
+                self.invert(a)
+            }
+
+            Expr::And(a, b) | Expr::Or(a, b) | Expr::Xor(a, b) => {
+                let (a, b) = (self.expr(a), self.expr(b));
+
+                match (expr, self.nand_only) {
+                    (Expr::And(..), false) => self.gate(NodeKind::And, vec![a, b]),
+                    (Expr::Or(..), false) => self.gate(NodeKind::Or, vec![a, b]),
+                    (_, false) => self.gate(NodeKind::XOr, vec![a, b]),
+
+                    (Expr::And(..), true) => {
+                        let nand = self.nand(a, b);
+                        self.invert(nand)
+                    }
+                    (Expr::Or(..), true) => {
+                        let (a, b) = (self.invert(a), self.invert(b));
+                        self.nand(a, b)
+                    }
+                    (_, true) => {
+                        let nand = self.nand(a, b);
+                        let (a, b) = (self.nand(a, nand), self.nand(b, nand));
+                        self.nand(a, b)
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expr;
+
+    #[test]
+    fn constants_are_not_inputs() {
+        let equations = expr::parse_equations("out = a | 1").unwrap();
+        let (nodes, _) = synthesize(&equations, false, Vec2::ZERO, &mut IdAllocator::new());
+
+        let inputs = nodes
+            .iter()
+            .filter(|node| matches!(node.kind, NodeKind::Input(_)))
+            .map(|node| node.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(inputs, ["a"]);
+        assert!(
+            nodes.iter().any(
+                |node| matches!(node.kind, NodeKind::Constant(value) if value == Value::new(1))
+            )
+        );
+    }
+}
@@ -0,0 +1,141 @@
+use crate::eval::{DependencyGraph, Evaluator};
+use crate::logic::Value;
+use crate::wire::*;
+
+/// Input combinations enumerated at most, circuits with more input bits get a partial table
+pub const MAX_ROWS: usize = 1 << 12;
+
+#[derive(Debug, Clone)]
+pub struct Column {
+    pub name: String,
+    pub width: u8,
+}
+
+/// Display values for every combination of the Input nodes of a circuit.
+///
+/// The first input is the most significant, rows are in counting order.
+#[derive(Debug, Clone, Default)]
+pub struct TruthTable {
+    pub inputs: Vec<Column>,
+    pub outputs: Vec<Column>,
+    /// Input values followed by output values
+    pub rows: Vec<Vec<Value>>,
+    /// Sum of the input widths
+    pub input_bits: u32,
+}
+
+impl TruthTable {
+    /// Evaluates a copy of the circuit for every input combination, the circuit itself is left
+    /// untouched. Each row starts from the current state of flip-flops and latches.
+    pub fn new(nodes: &[Node], dep_graph: &DependencyGraph, tick: u64) -> Self {
+        let columns = |is_column: fn(&NodeKind) -> bool| {
+            nodes
+                .iter()
+                .filter(|node| is_column(&node.kind))
+                .map(|node| Column {
+                    name: node.name.clone(),
+                    width: node.width,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let inputs = columns(|kind| matches!(kind, NodeKind::Input(_)));
+        let outputs = columns(|kind| matches!(kind, NodeKind::Display(_)));
+        let input_bits = inputs.iter().map(|input| input.width as u32).sum::<u32>();
+
+        let rows = (0..Self::row_count(input_bits) as u64)
+            .map(|row| {
+                let nodes = nodes.to_vec();
+
+                // the last input takes the lowest bits of the row number
+                let mut rest = row;
+                let mut values = vec![];
+                for node in nodes.iter().rev() {
+                    if let NodeKind::Input(value) = &node.kind {
+                        value.set(Value::new(rest & mask(node.width)));
+                        values.push(value.get());
+                        rest = rest.checked_shr(node.width as u32).unwrap_or(0);
+                    }
+                }
+                values.reverse();
+
+                Evaluator::new().evaluate(&nodes, dep_graph, tick);
+
+                values.extend(nodes.iter().filter_map(|node| match &node.kind {
+                    NodeKind::Display(value) => Some(value.get()),
+                    _ => None,
+                }));
+                values
+            })
+            .collect();
+
+        Self {
+            inputs,
+            outputs,
+            rows,
+            input_bits,
+        }
+    }
+
+    fn row_count(input_bits: u32) -> usize {
+        match input_bits {
+            bits if bits < MAX_ROWS.ilog2() => 1 << bits,
+            _ => MAX_ROWS,
+        }
+    }
+
+    /// `true` if there were too many input combinations to list them all
+    pub fn is_truncated(&self) -> bool {
+        self.input_bits > MAX_ROWS.ilog2()
+    }
+
+    pub fn columns(&self) -> impl Iterator<Item = &Column> {
+        self.inputs.iter().chain(&self.outputs)
+    }
+
+    /// Cells of a row as text, in column order
+    pub fn cells(&self, row: usize) -> impl Iterator<Item = String> {
+        self.rows[row]
+            .iter()
+            .zip(self.columns())
+            .map(|(value, column)| value.format(column.width))
+    }
+
+    pub fn to_csv(&self) -> String {
+        let escape = |field: &str| match field.contains([',', '"', '\n']) {
+            true => format!("\"{}\"", field.replace('"', "\"\"")),
+            false => field.to_string(),
+        };
+
+        let mut csv = self
+            .columns()
+            .map(|column| escape(&column.name))
+            .collect::<Vec<_>>()
+            .join(",");
+        csv.push('\n');
+
+        for row in 0..self.rows.len() {
+            csv += &self.cells(row).collect::<Vec<_>>().join(",");
+            csv.push('\n');
+        }
+
+        csv
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let line = |cells: Vec<String>| format!("| {} |\n", cells.join(" | "));
+
+        let mut markdown = line(
+            self.columns()
+                .map(|column| column.name.replace('|', "\\|"))
+                .collect(),
+        );
+        markdown += &line(self.columns().map(|_| "---".to_string()).collect());
+
+        for row in 0..self.rows.len() {
+            markdown += &line(self.cells(row).collect());
+        }
+
+        markdown
+    }
+}
@@ -1,10 +1,24 @@
 use std::cell::{Cell, RefCell};
 
-use raylib::math::Vector2;
+use glam::Vec2;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize};
 
-use crate::app::{App, DependencyGraph};
+use crate::chip::Chip;
+use crate::logic::{Logic, Value};
 
-#[derive(Debug, Clone)]
+/// Widest bus a socket can carry, values are stored in a `u64`
+pub const MAX_WIDTH: u8 = 64;
+
+/// Bit mask covering the lowest `width` bits
+pub fn mask(width: u8) -> u64 {
+    match width {
+        MAX_WIDTH.. => u64::MAX,
+        width => (1 << width) - 1,
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum NodeKind {
     NAnd,
     And,
@@ -12,8 +26,88 @@ pub enum NodeKind {
     XOr,
     Not,
 
-    Input(Cell<bool>),
-    Display(Cell<bool>),
+    Input(#[serde(deserialize_with = "deserialize_value")] Cell<Value>),
+    Display(#[serde(deserialize_with = "deserialize_value")] Cell<Value>),
+    /// Always drives the same value
+    Constant(Value),
+
+    /// Fans a bus out into one line per bit, bit 0 first
+    Splitter,
+    /// Combines one line per bit back into a bus
+    Joiner,
+    /// Buffer that stops driving its output while EN is low
+    TriState,
+
+    /// High for `duty * period` ticks out of every `period` simulation ticks
+    Clock {
+        #[serde(deserialize_with = "deserialize_period")]
+        period: Cell<u32>,
+        #[serde(deserialize_with = "deserialize_duty")]
+        duty: Cell<f32>,
+    },
+
+    // rising edge triggered, with asynchronous set and reset
+    DFlipFlop(Memory),
+    JKFlipFlop(Memory),
+    TFlipFlop(Memory),
+
+    // level sensitive
+    SRLatch(Memory),
+    DLatch(Memory),
+
+    /// User defined subcircuit
+    Chip(Box<Chip>),
+}
+
+/// Internal state of flip-flops and latches
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Memory {
+    pub q: Cell<bool>,
+    /// Clock level seen during the previous update, used for edge detection
+    pub clock: Cell<bool>,
+}
+
+/// Older circuits stored a single `bool` or the bits of a bus for inputs and displays
+fn deserialize_value<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Cell<Value>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Stored {
+        Bool(bool),
+        Bits(u64),
+        Value(Value),
+    }
+
+    Ok(match Stored::deserialize(deserializer)? {
+        Stored::Bool(value) => Value::new(value as u64),
+        Stored::Bits(bits) => Value::new(bits),
+        Stored::Value(value) => value,
+    }
+    .into())
+}
+
+/// Files are checked on load, the simulation keeps them in range as well
+fn deserialize_period<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Cell<u32>, D::Error> {
+    match u32::deserialize(deserializer)? {
+        0 => Err(D::Error::custom("clock period must be at least 1 tick")),
+        period => Ok(period.into()),
+    }
+}
+
+fn deserialize_duty<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Cell<f32>, D::Error> {
+    match f32::deserialize(deserializer)? {
+        duty if (0.0..=1.0).contains(&duty) => Ok(duty.into()),
+        duty => Err(D::Error::custom(format!(
+            "clock duty {duty} is not between 0 and 1"
+        ))),
+    }
+}
+
+impl Memory {
+    /// Records the clock level and reports whether it just went from low to high
+    fn rising_edge(&self, clock: Option<bool>) -> bool {
+        let clock = clock.unwrap_or(false);
+        !self.clock.replace(clock) && clock
+    }
 }
 
 impl std::fmt::Display for NodeKind {
@@ -29,26 +123,104 @@ impl std::fmt::Display for NodeKind {
                 NodeKind::Not => "NOT",
                 NodeKind::Input(_) => "INPUT",
                 NodeKind::Display(_) => "DISPLAY",
+                NodeKind::Constant(_) => "CONSTANT",
+                NodeKind::Splitter => "SPLITTER",
+                NodeKind::Joiner => "JOINER",
+                NodeKind::TriState => "TRI-STATE",
+                NodeKind::Clock { .. } => "CLOCK",
+                NodeKind::DFlipFlop(_) => "D FLIP-FLOP",
+                NodeKind::JKFlipFlop(_) => "JK FLIP-FLOP",
+                NodeKind::TFlipFlop(_) => "T FLIP-FLOP",
+                NodeKind::SRLatch(_) => "SR LATCH",
+                NodeKind::DLatch(_) => "D LATCH",
+                NodeKind::Chip(chip) => &chip.name,
             }
         )
     }
 }
 
 impl NodeKind {
-    pub fn list() -> [NodeKind; 7] {
+    pub fn list() -> [NodeKind; 16] {
         use NodeKind::*;
         [
-            Input(false.into()),
-            Display(false.into()),
+            Input(Value::new(0).into()),
+            Display(Value::Z.into()),
+            Clock {
+                period: 2.into(),
+                duty: 0.5.into(),
+            },
             NAnd,
             And,
             Not,
             Or,
             XOr,
+            TriState,
+            Splitter,
+            Joiner,
+            DFlipFlop(Memory::default()),
+            JKFlipFlop(Memory::default()),
+            TFlipFlop(Memory::default()),
+            SRLatch(Memory::default()),
+            DLatch(Memory::default()),
         ]
     }
 
-    pub fn apply_binary(&self, a: bool, b: bool) -> Option<bool> {
+    /// Flip-flops and latches, their outputs only depend on their internal state
+    pub fn is_sequential(&self) -> bool {
+        matches!(
+            self,
+            NodeKind::DFlipFlop(_)
+                | NodeKind::JKFlipFlop(_)
+                | NodeKind::TFlipFlop(_)
+                | NodeKind::SRLatch(_)
+                | NodeKind::DLatch(_)
+        )
+    }
+
+    /// Nodes that have to be clocked after the circuit settles
+    pub fn has_state(&self) -> bool {
+        self.is_sequential() || matches!(self, NodeKind::Chip(_))
+    }
+
+    /// Nodes whose pins can be widened into buses, gates then operate bitwise
+    pub fn has_width(&self) -> bool {
+        matches!(
+            self,
+            NodeKind::NAnd
+                | NodeKind::And
+                | NodeKind::Or
+                | NodeKind::XOr
+                | NodeKind::Not
+                | NodeKind::Input(_)
+                | NodeKind::Display(_)
+                | NodeKind::Constant(_)
+                | NodeKind::Splitter
+                | NodeKind::Joiner
+                | NodeKind::TriState
+        )
+    }
+
+    /// Width new nodes of this kind start out with
+    pub fn default_width(&self) -> u8 {
+        match self {
+            NodeKind::Splitter | NodeKind::Joiner => 8,
+            _ => 1,
+        }
+    }
+
+    fn memory(&self) -> Option<&Memory> {
+        match self {
+            NodeKind::DFlipFlop(memory)
+            | NodeKind::JKFlipFlop(memory)
+            | NodeKind::TFlipFlop(memory)
+            | NodeKind::SRLatch(memory)
+            | NodeKind::DLatch(memory) => Some(memory),
+            _ => None,
+        }
+    }
+
+    /// Bitwise, the evaluator masks the result to the width of the output
+    pub fn apply_binary(&self, a: Value, b: Value) -> Option<Value> {
         Some(match self {
             Self::NAnd => !(a & b),
             Self::And => a & b,
@@ -59,65 +231,256 @@ impl NodeKind {
         })
     }
 
-    pub fn inputs(&self) -> usize {
+    /// Computes the outputs of this node from the values of its input pins, unconnected pins
+    /// are Z and gates read them as X.
+    pub fn eval(&self, inputs: &[Value], tick: u64) -> Vec<Value> {
+        if let Some(memory) = self.memory() {
+            let q = memory.q.get();
+            return vec![Value::new(q as u64), Value::new(!q as u64)];
+        }
+
+        let input = |i: usize| inputs.get(i).copied().unwrap_or(Value::Z);
+
         match self {
-            NodeKind::Input(_) => 0,
-            NodeKind::Not | NodeKind::Display(_) => 1,
-            NodeKind::NAnd | NodeKind::And | NodeKind::Or | NodeKind::XOr => 2,
+            NodeKind::Chip(chip) => return chip.eval(inputs, tick),
+
+            // one value per possible bit, the evaluator only uses as many as there are outputs
+            NodeKind::Splitter => {
+                return (0..MAX_WIDTH).map(|bit| input(0).bit(bit).into()).collect();
+            }
+
+            _ => {}
+        }
+
+        vec![match self {
+            NodeKind::Input(value) => value.get(),
+            NodeKind::Constant(value) => *value,
+            NodeKind::Clock { period, duty } => {
+                let period = period.get().max(1);
+                let high = (period as f32 * duty.get().clamp(0.0, 1.0)).round() as u64;
+                Value::new((tick % (period as u64) < high) as u64)
+            }
+            NodeKind::Not => !input(0),
+            NodeKind::NAnd | NodeKind::And | NodeKind::Or | NodeKind::XOr => {
+                self.apply_binary(input(0), input(1)).unwrap()
+            }
+
+            // passes the input through while enabled, lets go of the wire otherwise
+            NodeKind::TriState => match input(1).bit(0) {
+                Logic::One => input(0).buffer(),
+                Logic::Zero => Value::Z,
+                Logic::X | Logic::Z => Value::X,
+            },
+
+            NodeKind::Display(output) => {
+                output.set(input(0));
+                input(0)
+            }
+
+            NodeKind::Joiner => {
+                let mut bus = Value::new(0);
+                for bit in 0..inputs.len() as u8 {
+                    bus.set_bit(bit, input(bit as usize).bit(0));
+                }
+                bus
+            }
+
+            _ => Value::X,
+        }]
+    }
+
+    /// Clocks the internal state of flip-flops and latches, returns `true` if it changed.
+    ///
+    /// Set/reset pins that are X or Z count as low, such a data input holds the current state.
+    pub fn update(&self, inputs: &[Value], tick: u64) -> bool {
+        if let NodeKind::Chip(chip) = self {
+            return chip.update(inputs, tick);
         }
+
+        let Some(memory) = self.memory() else {
+            return false;
+        };
+
+        let pin = |i: usize| inputs.get(i).and_then(|value| value.to_bool());
+        let high = |i: usize| pin(i).unwrap_or(false);
+        let q = memory.q.get();
+
+        let next = match self {
+            NodeKind::DFlipFlop(_) | NodeKind::TFlipFlop(_) => {
+                let edge = memory.rising_edge(pin(1));
+
+                if high(3) {
+                    false
+                } else if high(2) {
+                    true
+                } else if !edge {
+                    q
+                } else if let NodeKind::DFlipFlop(_) = self {
+                    pin(0).unwrap_or(q)
+                } else {
+                    q ^ high(0)
+                }
+            }
+
+            NodeKind::JKFlipFlop(_) => {
+                let edge = memory.rising_edge(pin(1));
+
+                if high(4) {
+                    false
+                } else if high(3) {
+                    true
+                } else if !edge {
+                    q
+                } else {
+                    match (high(0), high(2)) {
+                        (false, false) => q,
+                        (true, false) => true,
+                        (false, true) => false,
+                        (true, true) => !q,
+                    }
+                }
+            }
+
+            // reset wins if both are high
+            NodeKind::SRLatch(_) => !high(1) && (high(0) || q),
+
+            NodeKind::DLatch(_) => match high(1) {
+                true => pin(0).unwrap_or(q),
+                false => q,
+            },
+
+            _ => q,
+        };
+
+        memory.q.set(next);
+        next != q
+    }
+
+    /// Names and widths of the input pins for a node that is `width` bits wide
+    pub fn input_pins(&self, width: u8) -> Vec<(String, u8)> {
+        let names: &[&str] = match self {
+            NodeKind::DFlipFlop(_) => &["D", "CLK", "S", "R"],
+            NodeKind::JKFlipFlop(_) => &["J", "CLK", "K", "S", "R"],
+            NodeKind::TFlipFlop(_) => &["T", "CLK", "S", "R"],
+            NodeKind::SRLatch(_) => &["S", "R"],
+            NodeKind::DLatch(_) => &["D", "EN"],
+
+            NodeKind::Input(_) | NodeKind::Constant(_) | NodeKind::Clock { .. } => &[],
+            NodeKind::Not | NodeKind::Display(_) | NodeKind::Splitter => &["i0"],
+            NodeKind::NAnd | NodeKind::And | NodeKind::Or | NodeKind::XOr => &["i0", "i1"],
+
+            NodeKind::TriState => return vec![("i0".to_string(), width), ("EN".to_string(), 1)],
+            NodeKind::Joiner => return bit_pins(width),
+            NodeKind::Chip(chip) => return chip.input_pins(),
+        };
+
+        pins(names, width)
     }
 
-    pub fn outputs(&self) -> usize {
+    /// Names and widths of the output pins for a node that is `width` bits wide
+    pub fn output_pins(&self, width: u8) -> Vec<(String, u8)> {
         match self {
-            NodeKind::Display(_) => 1,
-            NodeKind::Not
-            | NodeKind::Input(_)
-            | NodeKind::NAnd
-            | NodeKind::And
-            | NodeKind::Or
-            | NodeKind::XOr => 1,
+            NodeKind::Chip(chip) => chip.output_pins(),
+            NodeKind::Splitter => bit_pins(width),
+            _ if self.is_sequential() => pins(&["Q", "!Q"], 1),
+            _ => pins(&["o0"], width),
         }
     }
 
-    pub fn build<F: FnMut() -> usize>(&self, position: Vector2, mut id_salt: F) -> Node {
-        Node {
-            id: id_salt(),
+    pub fn build(&self, position: Vec2, ids: &mut IdAllocator) -> Node {
+        let mut node = Node {
+            id: ids.node(),
             name: self.to_string(),
             position: position.into(),
+            width: self.default_width(),
             kind: self.clone(),
-            inputs: (0..self.inputs())
-                .map(|i| {
-                    Socket {
-                        name: format!("i{i}"),
-                        id: id_salt(),
-                        kind: SocketKind::Input,
-                        absolute_position: None,
-                    }
-                    .into()
-                })
-                .collect::<Vec<_>>(),
-            outputs: (0..self.outputs())
-                .map(|i| {
-                    Socket {
-                        name: format!("o{i}"),
-                        id: id_salt(),
-                        kind: SocketKind::Output,
-                        absolute_position: None,
-                    }
-                    .into()
-                })
-                .collect::<Vec<_>>(),
+            inputs: vec![],
+            outputs: vec![],
+        };
+
+        node.rebuild(ids);
+        node
+    }
+}
+
+fn pins(names: &[&str], width: u8) -> Vec<(String, u8)> {
+    names.iter().map(|name| (name.to_string(), width)).collect()
+}
+
+/// Single bit lines of a splitter or joiner
+fn bit_pins(width: u8) -> Vec<(String, u8)> {
+    (0..width).map(|bit| (format!("b{bit}"), 1)).collect()
+}
+
+/// Identifies a node within its circuit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct NodeId(pub usize);
+
+/// Identifies a socket within its circuit, numbered apart from the nodes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SocketId(pub usize);
+
+impl std::fmt::Display for NodeId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Hands out the ids of a single circuit.
+///
+/// Ids only ever go up, those of deleted nodes aren't given out again so undoing the deletion
+/// can bring the node back as it was. Nodes from another circuit have their own ids and have to
+/// be renumbered with the allocator of the circuit they're merged into, see
+/// `CircuitFile::build`.
+#[derive(Debug, Clone, Default)]
+pub struct IdAllocator {
+    next_node: usize,
+    next_socket: usize,
+}
+
+impl IdAllocator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Continues after the ids already used by `nodes`, for a circuit loaded from a file
+    pub fn after(nodes: &[Node]) -> Self {
+        let mut ids = Self::new();
+        ids.reserve(nodes);
+        ids
+    }
+
+    /// Makes sure the ids used by `nodes` won't be handed out
+    pub fn reserve(&mut self, nodes: &[Node]) {
+        for node in nodes {
+            self.next_node = self.next_node.max(node.id.0 + 1);
+
+            for socket in node.inputs.iter().chain(&node.outputs) {
+                self.next_socket = self.next_socket.max(socket.borrow().id.0 + 1);
+            }
         }
     }
+
+    pub fn node(&mut self) -> NodeId {
+        self.next_node += 1;
+        NodeId(self.next_node - 1)
+    }
+
+    pub fn socket(&mut self) -> SocketId {
+        self.next_socket += 1;
+        SocketId(self.next_socket - 1)
+    }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct SocketRef {
-    pub node_id: usize,
-    pub socket_id: usize,
+    pub node_id: NodeId,
+    pub socket_id: SocketId,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Edge {
     pub from: SocketRef,
     pub to: SocketRef,
@@ -131,84 +494,176 @@ pub enum SocketKind {
 
 #[derive(Debug, Clone)]
 pub struct Node {
-    pub id: usize,
+    // read only, circuits index their nodes by it
+    id: NodeId,
     pub name: String,
-    pub position: RefCell<Vector2>,
+    pub position: RefCell<Vec2>,
+    /// Bus width of the node's pins, always 1 for kinds without `has_width`
+    pub width: u8,
     pub inputs: Vec<RefCell<Socket>>,
     pub outputs: Vec<RefCell<Socket>>,
     pub kind: NodeKind,
 }
 
 impl Node {
-    // FIXME: reduce recursion
-    pub fn eval(&self, app: &App, dep_graph: &DependencyGraph) -> Option<bool> {
-        let connections = dep_graph.get(&self.id)?.as_slice();
-        let nodes = app.nodes.borrow();
-
-        println!("{}", self.kind);
-
-        Some(match &self.kind {
-            NodeKind::Input(val) => val.get(),
-            NodeKind::Not => {
-                let a = connections.first()?;
-                for node in nodes.iter() {
-                    if node.id == a.1 {
-                        return Some(!node.eval(app, dep_graph)?);
-                    }
-                }
+    /// Node as it was saved, with the ids it had
+    pub fn restore(
+        id: NodeId,
+        name: String,
+        position: Vec2,
+        width: u8,
+        kind: NodeKind,
+        inputs: Vec<RefCell<Socket>>,
+        outputs: Vec<RefCell<Socket>>,
+    ) -> Self {
+        Self {
+            id,
+            name,
+            position: position.into(),
+            width,
+            inputs,
+            outputs,
+            kind,
+        }
+    }
 
-                return None;
-            }
+    pub fn id(&self) -> NodeId {
+        self.id
+    }
 
-            NodeKind::NAnd | NodeKind::And | NodeKind::Or | NodeKind::XOr => {
-                let mut iter = connections
-                    .iter()
-                    .filter(|&&(kind, id)| kind == SocketKind::Input);
-
-                let a = iter.next()?.1;
-                let b = iter.next()?.1;
-
-                let [mut a_node, mut b_node] = [None; 2];
-                for node in nodes.iter() {
-                    if node.id == a {
-                        a_node = Some(node);
-                    } else if node.id == b {
-                        b_node = Some(node);
-                    }
-                }
+    /// Switches to another kind, sockets with the same name as before keep their ids so the
+    /// wires attached to them stay valid.
+    pub fn set_kind(&mut self, kind: NodeKind, ids: &mut IdAllocator) {
+        if !kind.has_width() {
+            self.width = 1;
+        }
 
-                self.kind
-                    .apply_binary(a_node?.eval(app, dep_graph)?, b_node?.eval(app, dep_graph)?)?
-            }
+        self.kind = kind;
+        self.rebuild(ids);
+    }
 
-            NodeKind::Display(output) => {
-                let a = connections
-                    .iter()
-                    .find(|&&(kind, id)| kind == SocketKind::Input)?;
-                println!("eval output");
-
-                let mut b = None;
-                for node in nodes.iter() {
-                    if node.id == a.1 {
-                        println!("eval {} for output", node.kind);
-                        b = Some(node.eval(app, dep_graph)?);
-                        output.set(b.unwrap());
-                        break;
+    /// Resizes the pins of a bus capable node, wires stay attached to sockets with the same
+    /// name even if their width no longer matches.
+    pub fn set_width(&mut self, width: u8, ids: &mut IdAllocator) {
+        self.width = width.clamp(1, MAX_WIDTH);
+        self.rebuild(ids);
+    }
+
+    /// Recreates the sockets for the current kind and width, reusing the ids of sockets that
+    /// keep their name. Pins sharing a name (chip pins of unrenamed nodes) are matched up in
+    /// order, every old id is given out at most once.
+    fn rebuild(&mut self, ids: &mut IdAllocator) {
+        let mut rebuild = |sockets: &[RefCell<Socket>], pins: Vec<(String, u8)>, kind| {
+            let mut taken = vec![false; sockets.len()];
+
+            pins.into_iter()
+                .map(|(name, width)| {
+                    let old = (0..sockets.len())
+                        .find(|&idx| !taken[idx] && sockets[idx].borrow().name == name);
+                    let id = match old {
+                        Some(idx) => {
+                            taken[idx] = true;
+                            sockets[idx].borrow().id
+                        }
+                        None => ids.socket(),
+                    };
+
+                    Socket {
+                        name,
+                        id,
+                        width,
+                        kind,
+                        absolute_position: None,
                     }
-                }
+                    .into()
+                })
+                .collect()
+        };
 
-                b?
-            }
-        })
+        self.inputs = rebuild(
+            &self.inputs,
+            self.kind.input_pins(self.width),
+            SocketKind::Input,
+        );
+        self.outputs = rebuild(
+            &self.outputs,
+            self.kind.output_pins(self.width),
+            SocketKind::Output,
+        );
+    }
+
+    pub fn socket(&self, socket_id: SocketId) -> Option<&RefCell<Socket>> {
+        self.inputs
+            .iter()
+            .chain(&self.outputs)
+            .find(|socket| socket.borrow().id == socket_id)
+    }
+
+    /// Name of the node, with its id if another node in `nodes` has the same name
+    pub fn label(&self, nodes: &[Node]) -> String {
+        match nodes.iter().filter(|other| other.name == self.name).count() {
+            0 | 1 => self.name.clone(),
+            _ => format!("{}#{}", self.name, self.id),
+        }
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct Socket {
     pub name: String,
-    pub id: usize,
+    pub id: SocketId,
+    /// Number of bits carried, wires can only connect sockets of the same width
+    pub width: u8,
     pub kind: SocketKind,
 
-    /// This is set once `draw_nodes` is called
-    pub absolute_position: Option<Vector2>,
+    /// Screen position of the pin, set once the node has been drawn
+    pub absolute_position: Option<Vec2>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rebuild_keeps_ids_of_pins_sharing_a_name() {
+        let mut ids = IdAllocator::new();
+        let a = NodeKind::Input(Cell::default()).build(Vec2::ZERO, &mut ids);
+        let b = NodeKind::Input(Cell::default()).build(Vec2::ZERO, &mut ids);
+        let inputs = vec![a.id, b.id];
+        let chip = Chip::new("chip".to_string(), vec![a, b], vec![], inputs, vec![]);
+
+        let mut node = NodeKind::Chip(Box::new(chip.clone())).build(Vec2::ZERO, &mut ids);
+        let socket_ids = |node: &Node| {
+            let inputs = node.inputs.iter().map(|socket| socket.borrow().id);
+            inputs.collect::<Vec<_>>()
+        };
+        let before = socket_ids(&node);
+        assert_ne!(before[0], before[1]);
+
+        node.set_kind(NodeKind::Chip(Box::new(chip)), &mut ids);
+        assert_eq!(socket_ids(&node), before);
+    }
+
+    #[test]
+    fn clocks_stay_within_their_period() {
+        let clock = |period: u32, duty: f32| NodeKind::Clock {
+            period: period.into(),
+            duty: duty.into(),
+        };
+        let levels = |kind: NodeKind| {
+            (0..4)
+                .map(|tick| kind.eval(&[], tick)[0])
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(levels(clock(2, 1.5)), vec![Value::new(1); 4]);
+        assert_eq!(levels(clock(0, -1.0)), vec![Value::new(0); 4]);
+
+        let load = |kind: NodeKind| {
+            let text = serde_json::to_string(&kind).unwrap();
+            serde_json::from_str::<NodeKind>(&text).is_ok()
+        };
+        assert!(load(clock(2, 0.5)));
+        assert!(!load(clock(0, 0.5)));
+        assert!(!load(clock(2, 1.5)));
+    }
 }
@@ -0,0 +1,76 @@
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+use glam::Vec2;
+use illogical::file::CircuitFile;
+use illogical::wire::*;
+
+fn wire(from: &Node, to: &Node, input: usize) -> Edge {
+    Edge {
+        from: SocketRef {
+            node_id: from.id(),
+            socket_id: from.outputs[0].borrow().id,
+        },
+        to: SocketRef {
+            node_id: to.id(),
+            socket_id: to.inputs[input].borrow().id,
+        },
+    }
+}
+
+/// Saves `a AND b -> out` to a file named after the test
+fn and_gate(test: &str) -> PathBuf {
+    let mut ids = IdAllocator::new();
+    let [mut a, mut b, gate, mut out] = [
+        NodeKind::Input(Default::default()),
+        NodeKind::Input(Default::default()),
+        NodeKind::And,
+        NodeKind::Display(Default::default()),
+    ]
+    .map(|kind| kind.build(Vec2::ZERO, &mut ids));
+    a.name = "a".into();
+    b.name = "b".into();
+    out.name = "out".into();
+
+    let edges = [wire(&a, &gate, 0), wire(&b, &gate, 1), wire(&gate, &out, 0)];
+    let path = std::env::temp_dir().join(format!("illogical-cli-{test}.json"));
+    CircuitFile::new(&[a, b, gate, out], edges)
+        .save(&path)
+        .unwrap();
+
+    path
+}
+
+fn cli(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_illogical-cli"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn help_goes_to_stdout() {
+    for flag in ["-h", "--help"] {
+        let output = cli(&[flag]);
+
+        assert!(output.status.success());
+        assert!(String::from_utf8_lossy(&output.stdout).starts_with("usage:"));
+        assert!(output.stderr.is_empty());
+    }
+}
+
+#[test]
+fn expectations_decide_the_exit_code() {
+    let path = and_gate("expectations");
+    let path = path.to_str().unwrap();
+
+    let output = cli(&[path, "--set", "a=1", "--set", "b=1", "--expect", "out=1"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "out=1\n");
+
+    let output = cli(&[path, "--set", "a=1", "--set", "b=0", "--expect", "out=1"]);
+    assert_eq!(output.status.code(), Some(1));
+
+    let output = cli(&["missing.json"]);
+    assert_eq!(output.status.code(), Some(2));
+}